@@ -0,0 +1,54 @@
+// Copyright 2023 PLM Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+use plm_core::DeprecateRequest;
+
+use crate::{
+    commands::install::parse_lib_requirement, registry::client::CliRegistryClientBuilder,
+    utils::prompter::Prompter, Deprecate,
+};
+
+pub async fn deprecate_command(
+    deprecate: Deprecate,
+    registry_url: String,
+    token: String,
+) -> Result<()> {
+    let (library, version_range) = parse_lib_requirement(&deprecate.library);
+
+    let mut client_builder = CliRegistryClientBuilder::new();
+    client_builder.with_addr(registry_url).with_token(token);
+    let mut client = client_builder.build().await?;
+
+    client
+        .deprecate(DeprecateRequest {
+            library: library.clone(),
+            version_range: version_range.clone().unwrap_or_default(),
+            message: deprecate.message.unwrap_or_default(),
+            replacement: deprecate.replacement.unwrap_or_default(),
+            undo: deprecate.undo,
+        })
+        .await?;
+
+    let target = match version_range {
+        Some(range) => format!("{}@{}", library, range),
+        None => library,
+    };
+    if deprecate.undo {
+        Prompter::info(&format!("Removed the deprecation of {}", target));
+    } else {
+        Prompter::info(&format!("Deprecated {}", target));
+    }
+    Ok(())
+}
@@ -130,7 +130,7 @@ fn write_manifest(path: &str, manifest: &plm_core::Manifest) -> PlmResult<()> {
 }
 
 fn create_directory(parent: &Path, dir: &str) -> PlmResult<PathBuf> {
-    let new_dir = FileSystem::join_paths(parent.clone(), dir);
+    let new_dir = FileSystem::join_paths(parent, dir);
     Prompter::verbose(&format!("creating {}", dir));
     FileSystem::create_dir(new_dir.clone().to_str().unwrap())
         .map_err(|_| PlmError::InternalError(format!("Failed to create directory {}", dir)))?;
@@ -12,14 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::path::Path;
+use std::{collections::HashSet, path::Path};
 
-use plm_core::{library::store::LibraryStore, plm::library::v1::Dependency, FileSystem, Manifest};
+use plm_core::{
+    library::store::LibraryStore,
+    plm::library::v1::Dependency,
+    registry::Registry,
+    utils::{hash_files, versioning},
+    FileSystem, Manifest,
+};
 
 use crate::{
-    registry::client::CliRegistryClientBuilder,
+    registry::client::{CliRegistryClient, CliRegistryClientBuilder},
     utils::{
-        lock::{Library, ProtoLock},
+        lock::{self, Library, ProtoLock},
         prompter::Prompter,
     },
     Install,
@@ -34,114 +40,265 @@ pub async fn install_command(
     registry_url: String,
     token: String,
 ) -> anyhow::Result<()> {
-    if let Some(lib_name) = install.name {
+    if install.locked {
+        return install_locked(manifest, proto_lock_path, proto_lock, registry_url, token).await;
+    }
+
+    if let Some(lib_ref) = install.name {
+        let (lib_name, requirement) = parse_lib_requirement(&lib_ref);
         validate_lib_name(&lib_name)?;
 
         Prompter::task(2, 6, &format!("Installing library -> {}", lib_name));
 
-        // TODO: Fetch Library from Registry
         Prompter::task(3, 6, "Fetching library from registry");
-
-        Prompter::task(4, 6, "Resolving dependencies");
-        // Resolve library deps
-        let resolved_deps = proto_lock.resolve_dependencies(lib_name.clone())?;
-
         let mut registry_client_builder = CliRegistryClientBuilder::new();
         registry_client_builder
-            .with_addr(registry_url)
+            .with_addr(registry_url.clone())
             .with_token(token);
         let mut client = registry_client_builder.build().await?;
 
-        let lib = LibraryStore::install(
+        Prompter::task(4, 6, "Resolving dependencies");
+        let installed = LibraryStore::fetch(
             Dependency {
                 library_id: lib_name.clone(),
-                version: "".to_string(),
+                version: requirement.clone().unwrap_or_default(),
+                packages: install.packages.clone(),
             },
             &mut client,
         )
         .await?;
-        // TODO: Download Dependencies
-        Prompter::task(5, 6, "Fetching dependencies");
-
-        // Add library to the proto-lock file
-        let installed_lib = Library {
-            name: lib_name,
-            version: lib.version,
-            dependencies: resolved_deps,
-        };
+        warn_deprecated(&mut client, &installed).await;
 
-        Prompter::task(6, 6, "Updating proto-lock.json file");
-        proto_lock.add_library(installed_lib.clone());
+        Prompter::task(5, 6, "Updating proto-lock.json file");
+        lock_installed(proto_lock, &installed, &registry_url, &install.packages)?;
         proto_lock.validate()?;
+        // A bare version is a caret requirement, so it keeps accepting compatible releases
+        let lib = &installed[0];
+        manifest.dependencies.insert(
+            lib.name.clone(),
+            requirement.unwrap_or_else(|| lib.version.clone()),
+        );
+        write_fetched(installed).await?;
         proto_lock.to_file(proto_lock_path)?;
 
-        manifest
-            .dependencies
-            .insert(installed_lib.clone().name, installed_lib.version);
-        // println!("{:?}", install);
-        let path = FileSystem::join_paths(manifest_path.clone(), "proto-package.json");
+        Prompter::task(6, 6, "Updating proto-package.json file");
+        let path = FileSystem::join_paths(manifest_path, "proto-package.json");
 
         FileSystem::write_json(path.to_str().unwrap(), &manifest)
             .map_err(|e| anyhow::anyhow!(e))?;
     } else {
-        #[allow(unreachable_code)]
-        let mut tree = ":: installing dependencies".to_string();
+        Prompter::info(":: installing dependencies");
 
         let mut registry_client_builder = CliRegistryClientBuilder::new();
         registry_client_builder
-            .with_addr(registry_url)
+            .with_addr(registry_url.clone())
             .with_token(token);
         let mut client = registry_client_builder.build().await?;
-        let dependency_count = manifest.dependencies.keys().len();
-        for (i, dep) in manifest.clone().dependencies.into_iter().enumerate() {
-            let lib = LibraryStore::install(
+        let mut fetched = vec![];
+        for (name, requirement) in manifest.dependencies.iter() {
+            // Libraries shared with a dependency fetched before are only downloaded once
+            let installed = LibraryStore::fetch_with(
                 Dependency {
-                    library_id: dep.0.clone(),
-                    version: "".to_string(),
+                    library_id: name.clone(),
+                    version: requirement.clone(),
+                    ..Default::default()
                 },
+                &fetched,
                 &mut client,
             )
             .await?;
-            let resolved_deps = proto_lock.resolve_dependencies(lib.name.clone())?;
-
-            // Add library to the proto-lock file
-            let installed_lib = Library {
-                name: lib.name,
-                version: lib.version,
-                dependencies: resolved_deps,
-            };
-
-            Prompter::task(6, 6, "Updating proto-lock.json file");
-            proto_lock.add_library(installed_lib.clone());
-            proto_lock.validate()?;
-            proto_lock.to_file(proto_lock_path)?;
-
-            manifest
-                .dependencies
-                .insert(installed_lib.clone().name, installed_lib.version.clone());
-
-            let tree_char = if i + 1 == dependency_count {
-                '┗'
-            } else {
-                '┣'
-            };
+            warn_deprecated(&mut client, &installed).await;
+
+            lock_installed(proto_lock, &installed, &registry_url, &[])?;
+            fetched.extend(installed);
+        }
 
-            tree.push_str(&format!(
-                "\n   {tree_char} installed {}@{}",
-                installed_lib.name, installed_lib.version
+        Prompter::task(6, 6, "Updating proto-lock.json file");
+        proto_lock.validate()?;
+        write_fetched(fetched).await?;
+        proto_lock.to_file(proto_lock_path)?;
+    }
+
+    Ok(())
+}
+
+/// Installs exactly the libraries pinned in proto-lock.json, without writing to it or to the
+/// manifest
+async fn install_locked(
+    manifest: &Manifest,
+    proto_lock_path: &Path,
+    proto_lock: &ProtoLock,
+    registry_url: String,
+    token: String,
+) -> anyhow::Result<()> {
+    if !proto_lock_path.is_file() {
+        return Err(anyhow::anyhow!(
+            "proto-lock.json is missing, run `plm install` to create it"
+        ));
+    }
+    proto_lock.check_manifest(&manifest.dependencies)?;
+
+    Prompter::info(":: installing locked dependencies");
+    let mut registry_client_builder = CliRegistryClientBuilder::new();
+    registry_client_builder
+        .with_addr(registry_url.clone())
+        .with_token(token);
+    let mut client = registry_client_builder.build().await?;
+
+    let mut installed = vec![];
+    for lib in &proto_lock.libraries {
+        if !lib.source.is_empty() && lib.source != registry_url {
+            return Err(anyhow::anyhow!(
+                "{}@{} is locked from {}, but the configured registry is {}",
+                lib.name,
+                lib.version,
+                lib.source,
+                registry_url
             ));
         }
-        Prompter::info(&tree);
+        let library = LibraryStore::install_locked(
+            Dependency {
+                library_id: lib.name.clone(),
+                version: lib.version.clone(),
+                packages: lib.packages.clone(),
+            },
+            &lib.checksum,
+            &mut client,
+        )
+        .await?;
+        Prompter::info(&format!("installed {}@{}", library.name, library.version));
+        installed.push(library);
+    }
+    warn_deprecated(&mut client, &installed).await;
 
-        let path = FileSystem::join_paths(manifest_path.clone(), "proto-package.json");
+    Ok(())
+}
 
-        FileSystem::write_json(path.to_str().unwrap(), &manifest)
-            .map_err(|e| anyhow::anyhow!(e))?;
+/// Warns about every installed library version its owners have deprecated
+pub(crate) async fn warn_deprecated(
+    client: &mut CliRegistryClient,
+    installed: &[plm_core::Library],
+) {
+    for lib in installed {
+        // Deprecation notices are advisory, an unreachable registry shouldn't fail the install
+        let Ok(metadata) = client.metadata(&lib.name).await else {
+            continue;
+        };
+        for deprecation in metadata
+            .deprecations
+            .iter()
+            .filter(|d| versioning::satisfies(&d.version_range, &lib.version))
+        {
+            let mut warning = format!(
+                "{}@{} is deprecated: {}",
+                lib.name, lib.version, deprecation.message
+            );
+            if !deprecation.replacement.is_empty() {
+                warning.push_str(&format!(", use {} instead", deprecation.replacement));
+            }
+            Prompter::warning(&warning);
+        }
+    }
+}
+
+/// Records a freshly installed dependency tree in the proto-lock file
+///
+/// `packages` are the protobuf packages the requested library, installed first, was narrowed to
+pub(crate) fn lock_installed(
+    proto_lock: &mut ProtoLock,
+    installed: &[plm_core::Library],
+    source: &str,
+    packages: &[String],
+) -> anyhow::Result<()> {
+    for (index, lib) in installed.iter().enumerate() {
+        let mut dependencies: Vec<lock::Dependency> = lib
+            .dependencies
+            .iter()
+            .map(|(name, range)| lock::Dependency {
+                name: name.clone(),
+                version: installed
+                    .iter()
+                    .find(|l| &l.name == name)
+                    .map(|l| l.version.clone())
+                    .unwrap_or_else(|| range.clone()),
+                requirement: if range.is_empty() {
+                    "*".to_string()
+                } else {
+                    range.clone()
+                },
+            })
+            .collect();
+        dependencies.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let locked = Library {
+            name: lib.name.clone(),
+            version: lib.version.clone(),
+            source: source.to_string(),
+            checksum: hash_files(lib.packages.iter().flat_map(|pkg| &pkg.files)),
+            packages: if index == 0 {
+                packages.to_vec()
+            } else {
+                vec![]
+            },
+            dependencies,
+        };
+        match proto_lock.find_library(Some(locked.name.clone())) {
+            // The same release must have the same content as when it was locked
+            Some(existing)
+                if existing.version == locked.version
+                    && existing.packages == locked.packages
+                    && !existing.checksum.is_empty()
+                    && existing.checksum != locked.checksum =>
+            {
+                return Err(anyhow::anyhow!(
+                    "checksum mismatch for {}@{}: locked {}, got {}",
+                    locked.name,
+                    locked.version,
+                    existing.checksum,
+                    locked.checksum
+                ));
+            }
+            Some(_) => proto_lock.update_library(locked.clone()),
+            None => proto_lock.add_library(locked.clone()),
+        }
+
+        // Dependents locked earlier now use this version, whether their requirement accepts it
+        // is up to `ProtoLock::validate`
+        for dependency in proto_lock
+            .libraries
+            .iter_mut()
+            .flat_map(|lib| lib.dependencies.iter_mut())
+            .filter(|dependency| dependency.name == locked.name)
+        {
+            dependency.version = locked.version.clone();
+        }
     }
+    Ok(())
+}
 
+/// Writes fetched libraries into `proto_modules`, once the proto-lock file recording them is
+/// known to be valid. A library fetched more than once is written as it was last locked
+pub(crate) async fn write_fetched(fetched: Vec<plm_core::Library>) -> anyhow::Result<()> {
+    let mut written = HashSet::new();
+    for lib in fetched.into_iter().rev() {
+        if written.insert(lib.name.clone()) {
+            LibraryStore::write(lib).await?;
+        }
+    }
     Ok(())
 }
 
+/// Splits `<lib>@<requirement>` (or `@org/<lib>@<requirement>`) into its name and requirement
+pub fn parse_lib_requirement(lib_ref: &str) -> (String, Option<String>) {
+    match lib_ref.rfind('@') {
+        Some(idx) if idx > 0 => (
+            lib_ref[..idx].to_string(),
+            Some(lib_ref[idx + 1..].to_string()).filter(|req| !req.is_empty()),
+        ),
+        _ => (lib_ref.to_string(), None),
+    }
+}
+
 fn validate_lib_name(name: &str) -> anyhow::Result<()> {
     if name.starts_with('@') {
         if !name
@@ -12,7 +12,6 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use anyhow::Context;
 use plm_core::LoginRequest;
 use tonic::Code;
 
@@ -24,7 +23,7 @@ use crate::{
 pub async fn login_command(
     configs: &mut CliConfigs,
     username: &str,
-    token: &str,
+    password: &str,
     registry_url: String,
 ) -> anyhow::Result<()> {
     Prompter::info("Processing login to registry");
@@ -33,33 +32,19 @@ pub async fn login_command(
     let mut client = client_builder.build().await?;
     let login = LoginRequest {
         username: username.to_string(),
-        token: token.to_string(),
+        password: password.to_string(),
     };
 
-    let login_response = client.login(login.clone()).await;
-    match login_response {
-        Err(e) => match e.code() {
-            Code::NotFound => {
-                Prompter::warning(e.message());
-                client
-                    .create_user(login)
-                    .await
-                    .with_context(|| format!("failed to login to registry: {:?}", registry_url))?;
-            }
-            Code::Unauthenticated => {
-                Prompter::error(e.message());
-            }
-            _ => Prompter::error(e.message()),
-        },
-        Ok(jwt) => {
-            Prompter::info(&format!(
-                "login successfully to registry: {}\n{:?}",
-                registry_url, jwt
-            ));
-            configs.token = Some(jwt.token);
-            configs.write_plmrc_file()?;
-            // TODO: Save verified username + pass to .plmrc global file
+    let jwt = client.login(login).await.map_err(|e| match e.code() {
+        Code::Unauthenticated => {
+            anyhow::anyhow!("{}, run `plm signup` to create an account", e.message())
         }
-    }
+        _ => anyhow::anyhow!(e.message().to_string()),
+    })?;
+
+    Prompter::info(&format!("login successfully to registry: {}", registry_url));
+    configs.username = Some(username.to_string());
+    configs.token = Some(jwt.token);
+    configs.write_plmrc_file()?;
     Ok(())
 }
@@ -0,0 +1,30 @@
+// Copyright 2023 PLM Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::utils::{configs::CliConfigs, prompter::Prompter};
+
+pub fn logout_command(configs: &mut CliConfigs) -> anyhow::Result<()> {
+    if configs.token.as_deref().unwrap_or_default().is_empty() {
+        Prompter::warning("Not logged in to any registry");
+    } else {
+        configs.token = None;
+        configs.write_plmrc_file()?;
+        Prompter::info(&format!("Logged out of registry: {}", configs.registry));
+    }
+
+    if std::env::var("PLM_TOKEN").is_ok_and(|token| !token.is_empty()) {
+        Prompter::warning("PLM_TOKEN is still set, and used to authenticate registry calls");
+    }
+    Ok(())
+}
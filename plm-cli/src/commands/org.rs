@@ -0,0 +1,82 @@
+// Copyright 2023 PLM Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+use plm_core::{AddUserRequest, RemoveUserRequest, Role, UpdateUserRoleRequest};
+
+use crate::{registry::client::CliRegistryClientBuilder, utils::prompter::Prompter, OrgCommand};
+
+pub async fn org_command(command: OrgCommand, registry_url: String, token: String) -> Result<()> {
+    let mut client_builder = CliRegistryClientBuilder::new();
+    client_builder.with_addr(registry_url).with_token(token);
+    let mut client = client_builder.build().await?;
+
+    match command {
+        OrgCommand::Create { name } => {
+            client.create_organization(&name).await?;
+            Prompter::info(&format!("Created organization {}", name));
+        }
+        OrgCommand::Show { org } => {
+            let org = client.get_organization(&org).await?;
+            Prompter::info(&format!("Members of {}:", org.name));
+            for user in org.users {
+                let role = Role::try_from(user.role).unwrap_or_default();
+                Prompter::normal(&format!(
+                    "{:<20} {}",
+                    user.username,
+                    role.as_str_name().to_lowercase()
+                ));
+            }
+        }
+        OrgCommand::Delete { org } => {
+            client.remove_organization(&org).await?;
+            Prompter::info(&format!("Deleted organization {}", org));
+        }
+        OrgCommand::Add { org, user, role } => {
+            client
+                .add_organization_user(AddUserRequest {
+                    org_id: org.clone(),
+                    user_id: user.clone(),
+                    role: Role::from(role).into(),
+                })
+                .await?;
+            Prompter::info(&format!("Added {} to {}", user, org));
+        }
+        OrgCommand::Remove { org, user } => {
+            client
+                .remove_organization_user(RemoveUserRequest {
+                    org_id: org.clone(),
+                    user_id: user.clone(),
+                })
+                .await?;
+            Prompter::info(&format!("Removed {} from {}", user, org));
+        }
+        OrgCommand::Role { org, user, role } => {
+            client
+                .update_organization_user_role(UpdateUserRoleRequest {
+                    org_id: org.clone(),
+                    user_id: user.clone(),
+                    role: Role::from(role).into(),
+                })
+                .await?;
+            Prompter::info(&format!(
+                "{} is now {} of {}",
+                user,
+                Role::from(role).as_str_name().to_lowercase(),
+                org
+            ));
+        }
+    }
+    Ok(())
+}
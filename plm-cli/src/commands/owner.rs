@@ -0,0 +1,57 @@
+// Copyright 2023 PLM Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+use plm_core::OwnerRequest;
+
+use crate::{registry::client::CliRegistryClientBuilder, utils::prompter::Prompter, OwnerCommand};
+
+pub async fn owner_command(
+    command: OwnerCommand,
+    registry_url: String,
+    token: String,
+) -> Result<()> {
+    let mut client_builder = CliRegistryClientBuilder::new();
+    client_builder.with_addr(registry_url).with_token(token);
+    let mut client = client_builder.build().await?;
+
+    match command {
+        OwnerCommand::Add { library, user } => {
+            client
+                .add_owner(OwnerRequest {
+                    library: library.clone(),
+                    username: user.clone(),
+                })
+                .await?;
+            Prompter::info(&format!("{} is now an owner of {}", user, library));
+        }
+        OwnerCommand::Remove { library, user } => {
+            client
+                .remove_owner(OwnerRequest {
+                    library: library.clone(),
+                    username: user.clone(),
+                })
+                .await?;
+            Prompter::info(&format!("{} is no longer an owner of {}", user, library));
+        }
+        OwnerCommand::List { library } => {
+            let owners = client.list_owners(&library).await?;
+            Prompter::info(&format!("Owners of {}:", library));
+            for owner in owners {
+                Prompter::normal(&owner);
+            }
+        }
+    }
+    Ok(())
+}
@@ -17,6 +17,7 @@ use anyhow::Result;
 use crate::{
     registry::client::CliRegistryClientBuilder,
     utils::{configs::CliConfigs, prompter::Prompter},
+    Access,
 };
 use plm_core::{library::store::LibraryStore, Manifest, PublishRequest};
 
@@ -25,6 +26,7 @@ pub async fn publish_command(
     configs: CliConfigs,
     token: String,
     preserve_imports: bool,
+    access: Option<Access>,
 ) -> Result<()> {
     let current_dir = &configs.current_dir;
     Prompter::info(format!("Publishing: {:<15}", manifest.name).as_str());
@@ -41,13 +43,22 @@ pub async fn publish_command(
         .with_addr(configs.registry.clone())
         .with_token(token);
     let mut client = registry_client_builder.build().await?;
+    Prompter::task(2, 3, "Uploading .proto files to registry");
+    let upload_token = client.upload(lib.clone()).await?;
+    // The files are already staged on the registry, so the release itself is sent without them
     let publish = PublishRequest {
-        lib: Some(lib.clone()),
+        lib: Some(plm_core::Library {
+            packages: vec![],
+            ..lib
+        }),
+        upload_token,
+        access: access
+            .map(plm_core::Access::from)
+            .unwrap_or(plm_core::Access::UnknownAccess)
+            .into(),
     };
-    Prompter::task(2, 3, "Creating new release for library");
+    Prompter::task(3, 3, "Creating new release for library");
     client.publish(publish).await?;
-    Prompter::task(3, 3, "Uploading .proto files to registry");
-    client.upload(lib).await?;
     Prompter::success(&format!("Library published: {}", configs.registry));
     Ok(())
 }
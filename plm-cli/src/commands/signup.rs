@@ -0,0 +1,57 @@
+// Copyright 2023 PLM Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use plm_core::CreateUserRequest;
+
+use crate::{
+    registry::client::CliRegistryClientBuilder,
+    utils::{configs::CliConfigs, prompter::Prompter},
+};
+
+use super::login::login_command;
+
+pub async fn signup_command(
+    configs: &mut CliConfigs,
+    username: &str,
+    password: &str,
+    email: &str,
+    registry_url: String,
+    admin_token: Option<String>,
+) -> anyhow::Result<()> {
+    Prompter::info("Processing signup to registry");
+    let mut client_builder = CliRegistryClientBuilder::new();
+    client_builder.with_addr(registry_url.clone());
+    if let Some(token) = &admin_token {
+        client_builder.with_token(token.clone());
+    }
+    let mut client = client_builder.build().await?;
+
+    let user = client
+        .create_user(CreateUserRequest {
+            username: username.to_string(),
+            password: password.to_string(),
+            email: email.to_string(),
+        })
+        .await?;
+    Prompter::info(&format!(
+        "Created user {} on registry: {}",
+        user.username, registry_url
+    ));
+
+    // Accounts provisioned by an admin are handed over, the admin stays logged in
+    if admin_token.is_some() {
+        return Ok(());
+    }
+    login_command(configs, username, password, registry_url).await
+}
@@ -0,0 +1,77 @@
+// Copyright 2023 PLM Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+use plm_core::CreateTokenRequest;
+
+use crate::{registry::client::CliRegistryClientBuilder, utils::prompter::Prompter, TokenCommand};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+pub async fn token_command(
+    command: TokenCommand,
+    registry_url: String,
+    token: String,
+) -> Result<()> {
+    let mut client_builder = CliRegistryClientBuilder::new();
+    client_builder.with_addr(registry_url).with_token(token);
+    let mut client = client_builder.build().await?;
+
+    match command {
+        TokenCommand::Create {
+            name,
+            scopes,
+            expires_in_days,
+        } => {
+            let response = client
+                .create_token(CreateTokenRequest {
+                    name: name.clone(),
+                    scopes,
+                    expires_in: expires_in_days.unwrap_or_default() * SECONDS_PER_DAY,
+                })
+                .await?;
+            Prompter::info(&format!(
+                "Created token {}, store it now as it won't be shown again:",
+                name
+            ));
+            Prompter::normal(&response.secret);
+        }
+        TokenCommand::List => {
+            let tokens = client.list_tokens().await?;
+            Prompter::info("API tokens:");
+            for token in tokens {
+                let expires = token
+                    .expires_at
+                    .map(|t| format!("expires {}", t))
+                    .unwrap_or_else(|| "never expires".to_string());
+                let last_used = token
+                    .last_used_at
+                    .map(|t| format!("last used {}", t))
+                    .unwrap_or_else(|| "never used".to_string());
+                Prompter::normal(&format!(
+                    "{:<20} {:<30} {}, {}",
+                    token.name,
+                    token.scopes.join(" "),
+                    expires,
+                    last_used
+                ));
+            }
+        }
+        TokenCommand::Revoke { name } => {
+            client.revoke_token(&name).await?;
+            Prompter::info(&format!("Revoked token {}", name));
+        }
+    }
+    Ok(())
+}
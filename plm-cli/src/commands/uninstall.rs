@@ -0,0 +1,105 @@
+// Copyright 2023 PLM Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::BTreeSet, path::Path};
+
+use plm_core::{library::store::LibraryStore, plm::library::v1::Dependency, FileSystem, Manifest};
+
+use crate::{
+    utils::{lock::ProtoLock, prompter::Prompter},
+    Uninstall,
+};
+
+pub async fn uninstall_command(
+    uninstall: Uninstall,
+    manifest: &mut Manifest,
+    manifest_path: &Path,
+    proto_lock_path: &Path,
+    proto_lock: &mut ProtoLock,
+) -> anyhow::Result<()> {
+    let lib_name = uninstall.name;
+    let dependency = Dependency {
+        library_id: lib_name.clone(),
+        ..Default::default()
+    };
+    let lib_dir = LibraryStore::locate(&dependency);
+    let installed = lib_dir.is_dir();
+
+    if !installed
+        && !manifest.dependencies.contains_key(&lib_name)
+        && proto_lock.find_library(Some(lib_name.clone())).is_none()
+    {
+        return Err(anyhow::anyhow!(
+            "Library {} is not installed under current lib: {}",
+            lib_name,
+            manifest.name
+        ));
+    }
+
+    Prompter::task(1, 4, "Checking for dependent libraries");
+    let mut dependents: BTreeSet<String> = proto_lock
+        .dependents(&lib_name)
+        .into_iter()
+        .map(|lib| format!("{}@{}", lib.name, lib.version))
+        .collect();
+    for dependent in LibraryStore::dependents(&lib_name).await? {
+        dependents.insert(format!("{}@{}", dependent.name, dependent.version));
+    }
+
+    if !dependents.is_empty() {
+        let list = dependents
+            .iter()
+            .map(|d| format!("\n   - {}", d))
+            .collect::<String>();
+        if !uninstall.force {
+            return Err(anyhow::anyhow!(
+                "Library {} is still required by:{}\nRe-run with --force to uninstall it anyway",
+                lib_name,
+                list
+            ));
+        }
+        Prompter::warning(&format!(
+            "Uninstalling {} although it is still required by:{}",
+            lib_name, list
+        ));
+    }
+
+    Prompter::task(2, 4, &format!("Removing {} from proto_modules", lib_name));
+    if installed {
+        LibraryStore::uninstall(&dependency).await?;
+    } else {
+        Prompter::warning(&format!(
+            "{} is missing from proto_modules",
+            lib_dir.display()
+        ));
+    }
+
+    Prompter::task(3, 4, "Updating proto-package.json file");
+    manifest.dependencies.remove(&lib_name);
+    let path = FileSystem::join_paths(manifest_path, "proto-package.json");
+    FileSystem::write_json(path.to_str().unwrap(), &manifest).map_err(|e| anyhow::anyhow!(e))?;
+
+    Prompter::task(4, 4, "Updating proto-lock.json file");
+    proto_lock.remove_library(&lib_name);
+    // A forced uninstall leaves its dependents dangling, until they are installed again
+    match proto_lock.validate() {
+        Err(e) if uninstall.force => Prompter::warning(&e.to_string()),
+        result => result?,
+    }
+    proto_lock.to_file(proto_lock_path)?;
+
+    Prompter::success(&format!("Uninstalled {}", lib_name));
+
+    Ok(())
+}
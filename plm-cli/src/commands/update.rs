@@ -11,3 +11,284 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::Path,
+};
+
+use plm_core::{library::store::LibraryStore, plm::library::v1::Dependency, FileSystem, Manifest};
+
+use crate::{
+    commands::install::{lock_installed, warn_deprecated, write_fetched},
+    registry::client::CliRegistryClientBuilder,
+    utils::{lock::ProtoLock, prompter::Prompter},
+    Update,
+};
+
+pub async fn update_command(
+    update: Update,
+    manifest: &mut Manifest,
+    manifest_path: &Path,
+    proto_lock_path: &Path,
+    proto_lock: &mut ProtoLock,
+    registry_url: String,
+    token: String,
+) -> anyhow::Result<()> {
+    let names: BTreeSet<String> = if update.names.is_empty() {
+        manifest.dependencies.keys().cloned().collect()
+    } else {
+        update.names.into_iter().collect()
+    };
+    if let Some(name) = names
+        .iter()
+        .find(|name| !manifest.dependencies.contains_key(*name))
+    {
+        return Err(anyhow::anyhow!(
+            "{} is not a dependency of {}, run `plm install {}` to add it",
+            name,
+            manifest.name,
+            name
+        ));
+    }
+    if names.is_empty() {
+        Prompter::info("No dependencies to update");
+        return Ok(());
+    }
+
+    let before: HashMap<String, String> = proto_lock
+        .libraries
+        .iter()
+        .map(|lib| (lib.name.clone(), lib.version.clone()))
+        .collect();
+
+    Prompter::task(1, 3, "Resolving dependencies");
+    let mut registry_client_builder = CliRegistryClientBuilder::new();
+    registry_client_builder
+        .with_addr(registry_url.clone())
+        .with_token(token);
+    let mut client = registry_client_builder.build().await?;
+
+    let mut updates = vec![];
+    let mut requirements = vec![];
+    // Whole libraries fetched so far, shared by the dependencies updated after them
+    let mut resolved: Vec<plm_core::Library> = vec![];
+    for name in &names {
+        // `--latest` ignores the manifest requirement, which is bumped to the installed release
+        let requirement = if update.latest {
+            String::new()
+        } else {
+            manifest.dependencies[name].clone()
+        };
+        let packages = proto_lock
+            .find_library(Some(name.clone()))
+            .map(|lib| lib.packages.clone())
+            .unwrap_or_default();
+
+        let installed = LibraryStore::fetch_with(
+            Dependency {
+                library_id: name.clone(),
+                version: requirement,
+                packages: packages.clone(),
+            },
+            &resolved,
+            &mut client,
+        )
+        .await?;
+        warn_deprecated(&mut client, &installed).await;
+        let skip = usize::from(!packages.is_empty());
+        resolved.extend(installed.iter().skip(skip).cloned());
+
+        let version = &installed[0].version;
+        if update.latest && &manifest.dependencies[name] != version {
+            requirements.push((name.clone(), version.clone()));
+        }
+        updates.push((installed, packages));
+    }
+
+    Prompter::task(2, 3, "Updating proto-lock.json file");
+    let mut dependencies = manifest.dependencies.clone();
+    dependencies.extend(requirements.iter().cloned());
+    let unused = lock_updates(proto_lock, &updates, &registry_url, &dependencies)?;
+    // `proto_modules` is only rewritten once the updated lock is known to be valid
+    write_fetched(
+        updates
+            .into_iter()
+            .flat_map(|(installed, _)| installed)
+            .collect(),
+    )
+    .await?;
+    for name in unused {
+        let dependency = Dependency {
+            library_id: name,
+            ..Default::default()
+        };
+        if LibraryStore::locate(&dependency).is_dir() {
+            LibraryStore::uninstall(&dependency).await?;
+        }
+    }
+    proto_lock.to_file(proto_lock_path)?;
+
+    Prompter::task(3, 3, "Updating proto-package.json file");
+    for (name, version) in &requirements {
+        Prompter::info(&format!(
+            "{} requirement: {} -> {}",
+            name, manifest.dependencies[name], version
+        ));
+    }
+    if !requirements.is_empty() {
+        manifest.dependencies = dependencies;
+        let path = FileSystem::join_paths(manifest_path, "proto-package.json");
+        FileSystem::write_json(path.to_str().unwrap(), &manifest)
+            .map_err(|e| anyhow::anyhow!(e))?;
+    }
+
+    print_changes(&before, proto_lock);
+    Ok(())
+}
+
+/// Locks the updated dependency trees, each with the packages its requested library is narrowed
+/// to, then drops the libraries no dependency requires anymore and validates the result.
+///
+/// Returns the dropped libraries
+fn lock_updates(
+    proto_lock: &mut ProtoLock,
+    updates: &[(Vec<plm_core::Library>, Vec<String>)],
+    source: &str,
+    dependencies: &HashMap<String, String>,
+) -> anyhow::Result<Vec<String>> {
+    for (installed, packages) in updates {
+        lock_installed(proto_lock, installed, source, packages)?;
+    }
+    let unused = proto_lock.prune(dependencies);
+    proto_lock.validate()?;
+    Ok(unused)
+}
+
+/// Prints the locked versions that changed, as a before/after table
+fn print_changes(before: &HashMap<String, String>, proto_lock: &ProtoLock) {
+    let after: HashMap<&str, &str> = proto_lock
+        .libraries
+        .iter()
+        .map(|lib| (lib.name.as_str(), lib.version.as_str()))
+        .collect();
+    let names: BTreeSet<&str> = before
+        .keys()
+        .map(String::as_str)
+        .chain(after.keys().copied())
+        .collect();
+
+    let changes: Vec<(&str, &str, &str)> = names
+        .into_iter()
+        .map(|name| {
+            (
+                name,
+                before.get(name).map(String::as_str).unwrap_or("-"),
+                after.get(name).copied().unwrap_or("-"),
+            )
+        })
+        .filter(|(_, before, after)| before != after)
+        .collect();
+    if changes.is_empty() {
+        Prompter::info("Dependencies are up to date");
+        return;
+    }
+
+    Prompter::normal(&format!("{:<30} {:<15} {}", "library", "before", "after"));
+    for (name, before, after) in changes {
+        Prompter::normal(&format!("{:<30} {:<15} {}", name, before, after));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::errors::PlmError;
+
+    const SOURCE: &str = "http://127.0.0.1:7575";
+
+    fn library(name: &str, version: &str, dependencies: &[(&str, &str)]) -> plm_core::Library {
+        plm_core::Library {
+            name: name.to_string(),
+            version: version.to_string(),
+            dependencies: dependencies
+                .iter()
+                .map(|(name, requirement)| (name.to_string(), requirement.to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    // `a` and `x` share `c`, and `a` also depends on `d`
+    fn installed_lock() -> ProtoLock {
+        let mut proto_lock = ProtoLock::default();
+        let trees = [
+            vec![
+                library("a", "1.0.0", &[("c", "^0.1.0"), ("d", "^1.0.0")]),
+                library("c", "0.1.0", &[]),
+                library("d", "1.0.0", &[]),
+            ],
+            vec![
+                library("x", "1.0.0", &[("c", "^0.1.0")]),
+                library("c", "0.1.0", &[]),
+            ],
+        ];
+        for installed in &trees {
+            lock_installed(&mut proto_lock, installed, SOURCE, &[]).unwrap();
+        }
+        proto_lock.validate().unwrap();
+        proto_lock
+    }
+
+    fn manifest() -> HashMap<String, String> {
+        HashMap::from([
+            ("a".to_string(), "^1.0.0".to_string()),
+            ("x".to_string(), "^1.0.0".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_lock_updates() {
+        // `a` bumps the shared `c` and no longer depends on `d`
+        let mut proto_lock = installed_lock();
+        let updates = [(
+            vec![
+                library("a", "1.1.0", &[("c", "^0.1.1")]),
+                library("c", "0.1.1", &[]),
+            ],
+            vec![],
+        )];
+
+        let unused = lock_updates(&mut proto_lock, &updates, SOURCE, &manifest()).unwrap();
+        assert_eq!(unused, ["d"]);
+        let locked: Vec<String> = proto_lock
+            .libraries
+            .iter()
+            .map(|lib| format!("{}@{}", lib.name, lib.version))
+            .collect();
+        assert_eq!(locked, ["a@1.1.0", "c@0.1.1", "x@1.0.0"]);
+        // The other dependent of `c` now uses the bumped version
+        let x = proto_lock.find_library(Some("x".to_string())).unwrap();
+        assert_eq!(x.dependencies[0].version, "0.1.1");
+    }
+
+    #[test]
+    fn test_lock_updates_conflict() {
+        // `x` doesn't accept the breaking release of `c` required by `a`
+        let mut proto_lock = installed_lock();
+        let updates = [(
+            vec![
+                library("a", "2.0.0", &[("c", "^0.2.0")]),
+                library("c", "0.2.0", &[]),
+            ],
+            vec![],
+        )];
+
+        let err = lock_updates(&mut proto_lock, &updates, SOURCE, &manifest()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PlmError>(),
+            Some(PlmError::InvalidLock(problems))
+                if problems == "x@1.0.0 -> c@^0.1.0, but c@0.2.0 is locked"
+        ));
+    }
+}
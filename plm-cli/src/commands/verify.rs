@@ -0,0 +1,61 @@
+// Copyright 2023 PLM Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use plm_core::{library::store::LibraryStore, plm::library::v1::Dependency};
+
+use crate::utils::{lock::ProtoLock, prompter::Prompter};
+
+/// Re-hashes the installed libraries and checks them against their locked checksums
+pub fn verify_command(proto_lock: &ProtoLock) -> anyhow::Result<()> {
+    let mut failed = 0;
+    for lib in &proto_lock.libraries {
+        if lib.checksum.is_empty() {
+            Prompter::warning(&format!(
+                "{}@{} has no locked checksum, skipped",
+                lib.name, lib.version
+            ));
+            continue;
+        }
+
+        let installed = LibraryStore::checksum_installed(&Dependency {
+            library_id: lib.name.clone(),
+            ..Default::default()
+        });
+        match installed {
+            Ok(checksum) if checksum == lib.checksum => {
+                Prompter::info(&format!("{}@{} ok", lib.name, lib.version));
+            }
+            Ok(checksum) => {
+                failed += 1;
+                Prompter::error(&format!(
+                    "{}@{} checksum mismatch: locked {}, installed {}",
+                    lib.name, lib.version, lib.checksum, checksum
+                ));
+            }
+            Err(e) => {
+                failed += 1;
+                Prompter::error(&format!("{}@{}: {}", lib.name, lib.version, e));
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} libraries failed verification, run `plm install --locked` to restore them",
+            failed,
+            proto_lock.libraries.len()
+        ));
+    }
+    Ok(())
+}
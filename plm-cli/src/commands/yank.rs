@@ -0,0 +1,52 @@
+// Copyright 2023 PLM Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+use plm_core::YankRequest;
+
+use crate::{
+    commands::install::parse_lib_requirement, registry::client::CliRegistryClientBuilder,
+    utils::prompter::Prompter, Yank,
+};
+
+pub async fn yank_command(yank: Yank, registry_url: String, token: String) -> Result<()> {
+    let (library, version) = match parse_lib_requirement(&yank.library) {
+        (library, Some(version)) => (library, version),
+        (library, None) => {
+            return Err(anyhow::anyhow!(
+                "must specify the version to yank: {}@<x.y.z>",
+                library
+            ))
+        }
+    };
+
+    let mut client_builder = CliRegistryClientBuilder::new();
+    client_builder.with_addr(registry_url).with_token(token);
+    let mut client = client_builder.build().await?;
+
+    client
+        .yank(YankRequest {
+            library: library.clone(),
+            version: version.clone(),
+            reason: yank.reason.unwrap_or_default(),
+            undo: yank.undo,
+        })
+        .await?;
+    if yank.undo {
+        Prompter::info(&format!("Restored {}@{}", library, version));
+    } else {
+        Prompter::info(&format!("Yanked {}@{}", library, version));
+    }
+    Ok(())
+}
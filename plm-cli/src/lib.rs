@@ -2,12 +2,21 @@ use clap::{Args, Parser, Subcommand, ValueEnum};
 
 pub mod commands {
     pub mod add;
+    pub mod deprecate;
     pub mod init;
     pub mod install;
     pub mod login;
+    pub mod logout;
+    pub mod org;
+    pub mod owner;
     pub mod publish;
     pub mod remove;
+    pub mod signup;
+    pub mod token;
+    pub mod uninstall;
     pub mod update;
+    pub mod verify;
+    pub mod yank;
 }
 
 pub mod registry {
@@ -75,12 +84,39 @@ pub enum Commands {
     /// Uninstalls a package
     Uninstall(Uninstall),
 
+    /// Updates dependencies to the newest releases their requirements allow
+    Update(Update),
+
+    /// Checks the installed libraries against the checksums of proto-lock.json
+    Verify,
+
     /// Publishes a package
     Publish(Publish),
 
+    /// Creates an account on the registry and logs into it
+    Signup(Signup),
+
     /// Saving login creds for the registry
     Login(Login),
 
+    /// Removes the saved login token
+    Logout,
+
+    /// Manages the users allowed to publish a library
+    Owner(OwnerArgs),
+
+    /// Manages organizations and their members
+    Org(OrgArgs),
+
+    /// Manages the API tokens used to publish from CI
+    Token(TokenArgs),
+
+    /// Yanks a published version, so it's no longer resolved by new installs
+    Yank(Yank),
+
+    /// Deprecates a library or a range of its versions, warning whoever installs them
+    Deprecate(Deprecate),
+
     /// Saving login creds for the registry
     Config(ConfigArgs),
     // Lists installed packages
@@ -119,6 +155,136 @@ pub enum ConfigCommand {
     },
 }
 
+#[derive(Debug, Args, Clone)]
+pub struct OwnerArgs {
+    #[command(subcommand)]
+    pub command: OwnerCommand,
+}
+
+/// Manage library owners
+#[derive(Debug, Subcommand, Clone)]
+pub enum OwnerCommand {
+    #[clap(about = "Allow a user to publish a library")]
+    Add {
+        /// The library name
+        library: String,
+        /// The username to add as owner
+        user: String,
+    },
+    #[clap(about = "Revoke a user permission to publish a library")]
+    Remove {
+        /// The library name
+        library: String,
+        /// The username to remove from the owners
+        user: String,
+    },
+    #[clap(about = "List the owners of a library")]
+    List {
+        /// The library name
+        library: String,
+    },
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct OrgArgs {
+    #[command(subcommand)]
+    pub command: OrgCommand,
+}
+
+/// Manage organizations
+#[derive(Debug, Subcommand, Clone)]
+pub enum OrgCommand {
+    #[clap(about = "Create a new organization, with yourself as its admin")]
+    Create {
+        /// The organization name
+        name: String,
+    },
+    #[clap(about = "Show an organization and its members")]
+    Show {
+        /// The organization name
+        org: String,
+    },
+    #[clap(about = "Delete an organization, it must not have any library published under it")]
+    Delete {
+        /// The organization name
+        org: String,
+    },
+    #[clap(about = "Add a user to an organization")]
+    Add {
+        /// The organization name
+        org: String,
+        /// The username to add
+        user: String,
+        /// The member role in the organization
+        #[arg(long, value_enum, default_value_t = OrgRole::Contributor)]
+        role: OrgRole,
+    },
+    #[clap(about = "Remove a user from an organization")]
+    Remove {
+        /// The organization name
+        org: String,
+        /// The username to remove
+        user: String,
+    },
+    #[clap(about = "Change the role of an organization member")]
+    Role {
+        /// The organization name
+        org: String,
+        /// The member username
+        user: String,
+        /// The new role of the member
+        #[arg(value_enum)]
+        role: OrgRole,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum OrgRole {
+    /// Manages the organization members, and publishes its libraries
+    Admin,
+
+    /// Publishes the organization libraries
+    Contributor,
+}
+
+impl From<OrgRole> for plm_core::Role {
+    fn from(role: OrgRole) -> Self {
+        match role {
+            OrgRole::Admin => plm_core::Role::Admin,
+            OrgRole::Contributor => plm_core::Role::Contributor,
+        }
+    }
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct TokenArgs {
+    #[command(subcommand)]
+    pub command: TokenCommand,
+}
+
+/// Manage API tokens
+#[derive(Debug, Subcommand, Clone)]
+pub enum TokenCommand {
+    #[clap(about = "Create a new API token, its secret is printed only once")]
+    Create {
+        /// The token name, unique per user
+        name: String,
+        /// The token scopes: read, publish:<library> or admin
+        #[arg(long = "scope", required = true)]
+        scopes: Vec<String>,
+        /// Expire the token after the given number of days
+        #[arg(long)]
+        expires_in_days: Option<u64>,
+    },
+    #[clap(about = "List your API tokens")]
+    List,
+    #[clap(about = "Revoke an API token")]
+    Revoke {
+        /// The token name
+        name: String,
+    },
+}
+
 #[derive(Debug, ValueEnum, Clone)]
 pub enum ConfigAction {
     Get,
@@ -172,33 +338,109 @@ pub enum License {
     UNLICENSE,
 }
 
-/// Create a new workspace
+/// Create a registry account
+#[derive(Debug, Args, Clone)]
+pub struct Signup {
+    /// The username to signup with
+    pub user: String,
+
+    /// The password to signup with, at least 8 characters
+    pub password: String,
+
+    /// The account email address
+    #[arg(long)]
+    pub email: String,
+
+    /// Creates the account with your own login as a registry admin, without logging into it
+    #[arg(long)]
+    pub admin: bool,
+}
+
+/// Login to the registry
 #[derive(Debug, Args, Clone)]
 pub struct Login {
-    /// The username to login/signup with
+    /// The username to login with
     pub user: String,
 
-    /// The password to login/signup with
+    /// The password to login with
     pub password: String,
 }
 
+/// Yank a published version
+#[derive(Debug, Args, Clone)]
+pub struct Yank {
+    /// The version to yank, in format: <library>@<x.y.z>
+    pub library: String,
+
+    /// Why the version is yanked, shown in its metadata
+    #[arg(long, conflicts_with = "undo")]
+    pub reason: Option<String>,
+
+    /// Restore a yanked version
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub undo: bool,
+}
+
+/// Deprecate a library or a range of its versions
+#[derive(Debug, Args, Clone)]
+pub struct Deprecate {
+    /// The library to deprecate, optionally narrowed to a semver range: <library>@<range>
+    pub library: String,
+
+    /// Why the library is deprecated, shown on install
+    #[arg(short, long, required_unless_present = "undo", conflicts_with = "undo")]
+    pub message: Option<String>,
+
+    /// A library to use instead
+    #[arg(long, conflicts_with = "undo")]
+    pub replacement: Option<String>,
+
+    /// Remove the deprecation of the library (or of the given range)
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub undo: bool,
+}
+
 /// Installs a package
 #[derive(Debug, Args, Clone)]
 #[command(arg_required_else_help = false, args_conflicts_with_subcommands = true)]
 pub struct Install {
-    /// The name of the package to install
+    /// The name of the package to install, optionally with a semver requirement: <name>@<requirement>
     pub name: Option<String>,
 
+    /// Only install the given protobuf packages of the library (and the files they import)
+    #[arg(long, value_delimiter = ',', requires = "name")]
+    pub packages: Vec<String>,
+
+    /// Install exactly the versions pinned in proto-lock.json, failing if it's missing or out of
+    /// date, without writing to it or to proto-package.json
+    #[arg(long, visible_alias = "frozen", conflicts_with = "name", action = clap::ArgAction::SetTrue)]
+    pub locked: bool,
+
     /// Verbose mode
     #[arg(short, long)]
     pub global: bool,
 }
 
+/// Updates dependencies
+#[derive(Debug, Args, Clone)]
+pub struct Update {
+    /// The dependencies to update, all of them when empty
+    pub names: Vec<String>,
+
+    /// Update to the latest releases, bumping the requirements of proto-package.json
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub latest: bool,
+}
+
 /// Uninstalls a package
 #[derive(Debug, Args, Clone)]
 pub struct Uninstall {
     /// The name of the package to uninstall
     pub name: String,
+
+    /// Uninstall even if other installed libraries still depend on it
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub force: bool,
 }
 
 /// Publishes a package
@@ -210,6 +452,28 @@ pub struct Publish {
     /// This would preserve the original import paths to ensure that the original structure is emulated within proto_modules/
     #[arg(long, action = clap::ArgAction::SetTrue)]
     pub preserve_imports: bool,
+
+    /// Who can install the library, defaults to its current access (new libraries are public, unless scoped to an organization)
+    #[arg(long, value_enum)]
+    pub access: Option<Access>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Access {
+    /// Anyone can install the library
+    Public,
+
+    /// Only the library owners and its organization members can install the library
+    Private,
+}
+
+impl From<Access> for plm_core::Access {
+    fn from(access: Access) -> Self {
+        match access {
+            Access::Public => plm_core::Access::Public,
+            Access::Private => plm_core::Access::Private,
+        }
+    }
 }
 
 pub fn parse_cli() -> Cli {
@@ -217,6 +481,6 @@ pub fn parse_cli() -> Cli {
 }
 // Re-export types for easier use in dependent code.
 pub use crate::{
-    commands::{add, init, install, remove, update},
+    commands::{add, init, install, remove, uninstall, update},
     utils::{helpers, tracing},
 };
@@ -19,6 +19,8 @@ use plm_core::FileSystem;
 use std::{collections::HashMap, io::Write as ioWrite};
 use tokio::{signal, sync::mpsc};
 
+// `human_panic` still expands to the deprecated `PanicInfo` alias
+#[allow(deprecated)]
 #[tokio::main]
 async fn main() -> PlmResult<()> {
     setup_panic!();
@@ -132,9 +134,12 @@ async fn process_commands(args: Cli, cfgs: &mut CliConfigs) -> anyhow::Result<()
             let mut manifest = get_manifest_from_file()?;
 
             Prompter::task(1, 6, "resolving proto-lock.json file");
-            let mut proto_lock =
-                ProtoLock::from_file(proto_lock_path.clone()).unwrap_or(ProtoLock::default());
-            if let Some(library) = proto_lock.find_library(install.name.clone()) {
+            let mut proto_lock = ProtoLock::from_file_or_default(&proto_lock_path)?;
+            let lib_name = install
+                .name
+                .as_deref()
+                .map(|lib_ref| commands::install::parse_lib_requirement(lib_ref).0);
+            if let Some(library) = proto_lock.find_library(lib_name) {
                 // Handle logic if package is already installed, perhaps prompt for update or exit
                 Prompter::warning(&format!(
                     "Package {} already exists under current lib: {}",
@@ -150,7 +155,7 @@ async fn process_commands(args: Cli, cfgs: &mut CliConfigs) -> anyhow::Result<()
                     &proto_lock_path,
                     &mut proto_lock,
                     cfgs.registry.clone(),
-                    cfgs.clone().token.unwrap_or_default(),
+                    cfgs.auth_token(),
                 )
                 .await
                 .with_context(|| "install command errored".to_string())?;
@@ -158,7 +163,49 @@ async fn process_commands(args: Cli, cfgs: &mut CliConfigs) -> anyhow::Result<()
         }
 
         // <-------- Uninstall -------->
-        Commands::Uninstall(_uninstall) => todo!(),
+        Commands::Uninstall(uninstall) => {
+            let proto_lock_path = proto_lock_path(cfgs);
+            let mut manifest = get_manifest_from_file()?;
+            let mut proto_lock = ProtoLock::from_file_or_default(&proto_lock_path)?;
+
+            commands::uninstall::uninstall_command(
+                uninstall,
+                &mut manifest,
+                FileSystem::current_dir().unwrap().as_path(),
+                &proto_lock_path,
+                &mut proto_lock,
+            )
+            .await
+            .with_context(|| "uninstall command errored".to_string())?;
+        }
+
+        // <-------- Update ----------->
+        Commands::Update(update) => {
+            let proto_lock_path = proto_lock_path(cfgs);
+            let mut manifest = get_manifest_from_file()?;
+            let mut proto_lock = ProtoLock::from_file_or_default(&proto_lock_path)?;
+
+            commands::update::update_command(
+                update,
+                &mut manifest,
+                FileSystem::current_dir().unwrap().as_path(),
+                &proto_lock_path,
+                &mut proto_lock,
+                cfgs.registry.clone(),
+                cfgs.auth_token(),
+            )
+            .await
+            .with_context(|| "update command errored".to_string())?;
+        }
+
+        // <-------- Verify ----------->
+        Commands::Verify => {
+            let proto_lock = ProtoLock::from_file(proto_lock_path(cfgs))
+                .with_context(|| "failed to read proto-lock.json".to_string())?;
+
+            commands::verify::verify_command(&proto_lock)
+                .with_context(|| "verify command errored".to_string())?;
+        }
 
         // <-------- Publish ---------->
         Commands::Publish(publish) => {
@@ -167,13 +214,28 @@ async fn process_commands(args: Cli, cfgs: &mut CliConfigs) -> anyhow::Result<()
             commands::publish::publish_command(
                 manifest,
                 cfgs.clone(),
-                cfgs.clone().token.unwrap_or_default(),
+                cfgs.auth_token(),
                 publish.preserve_imports,
+                publish.access,
             )
             .await
             .with_context(|| "publish command errored".to_string())?;
         }
 
+        // <-------- Signup ----------->
+        Commands::Signup(signup) => {
+            commands::signup::signup_command(
+                cfgs,
+                &signup.user,
+                &signup.password,
+                &signup.email,
+                cfgs.registry.clone(),
+                signup.admin.then(|| cfgs.auth_token()),
+            )
+            .await
+            .with_context(|| "signup command errored".to_string())?;
+        }
+
         // <-------- Login ------------>
         Commands::Login(login) => {
             commands::login::login_command(
@@ -186,6 +248,51 @@ async fn process_commands(args: Cli, cfgs: &mut CliConfigs) -> anyhow::Result<()
             .with_context(|| "login command errored".to_string())?;
         }
 
+        // <-------- Logout ----------->
+        Commands::Logout => {
+            commands::logout::logout_command(cfgs)
+                .with_context(|| "logout command errored".to_string())?;
+        }
+
+        // <-------- Owner ------------>
+        Commands::Owner(owner) => {
+            commands::owner::owner_command(owner.command, cfgs.registry.clone(), cfgs.auth_token())
+                .await
+                .with_context(|| "owner command errored".to_string())?;
+        }
+
+        // <-------- Org -------------->
+        Commands::Org(org) => {
+            commands::org::org_command(org.command, cfgs.registry.clone(), cfgs.auth_token())
+                .await
+                .with_context(|| "org command errored".to_string())?;
+        }
+
+        // <-------- Yank ------------->
+        Commands::Yank(yank) => {
+            commands::yank::yank_command(yank, cfgs.registry.clone(), cfgs.auth_token())
+                .await
+                .with_context(|| "yank command errored".to_string())?;
+        }
+
+        // <-------- Deprecate -------->
+        Commands::Deprecate(deprecate) => {
+            commands::deprecate::deprecate_command(
+                deprecate,
+                cfgs.registry.clone(),
+                cfgs.auth_token(),
+            )
+            .await
+            .with_context(|| "deprecate command errored".to_string())?;
+        }
+
+        // <-------- Token ------------>
+        Commands::Token(token) => {
+            commands::token::token_command(token.command, cfgs.registry.clone(), cfgs.auth_token())
+                .await
+                .with_context(|| "token command errored".to_string())?;
+        }
+
         // <-------- Config ----------->
         Commands::Config(cfg) => {
             if let Some(cmd) = cfg.command {
@@ -17,8 +17,14 @@ use std::{fmt::Write, process::exit, time::Duration};
 use indicatif::ProgressBar;
 // use anyhow::{Context, Ok};
 use plm_core::{
-    plm::registry::v1::UploadRequest, registry_service_client, user_service_client,
-    DownloadRequest, Library, LoginRequest, LoginResponse, ProtobufOrGz, PublishRequest,
+    organization_service_client,
+    plm::registry::v1::{Compressions, UploadRequest},
+    registry_service_client, user_service_client, AddUserRequest, ApiToken,
+    CreateOrganizationRequest, CreateTokenRequest, CreateTokenResponse, CreateUserRequest,
+    DeprecateRequest, DownloadRequest, GetOrganizationRequest, Library, ListOwnersRequest,
+    LoginRequest, LoginResponse, MetadataRequest, MetadataResponse, Organization, OwnerRequest,
+    PartialDownloadRequest, PublishRequest, RemoveOrganizationRequest, RemoveUserRequest,
+    RevokeTokenRequest, UpdateUserRoleRequest, YankRequest,
 };
 use tokio_stream::{Stream, StreamExt};
 use tonic::{
@@ -114,18 +120,31 @@ impl CliRegistryClientBuilder {
                     )
                 })?;
 
+        let orgs = organization_service_client::OrganizationServiceClient::with_interceptor(
+            channel.clone(),
+            AuthInterceptor {
+                token: token.clone(),
+            },
+        );
         let reg = registry_service_client::RegistryServiceClient::with_interceptor(
             channel.clone(),
-            AuthInterceptor { token },
+            AuthInterceptor {
+                token: token.clone(),
+            },
         )
         .max_decoding_message_size(100 * 1024 * 1024) // 100 MB
         .max_encoding_message_size(100 * 1024 * 1024); // 100 MB;
 
         // let reg = registry_service_client::RegistryServiceClient::new(channel.clone());
 
-        let user = user_service_client::UserServiceClient::new(channel);
+        let user = user_service_client::UserServiceClient::with_interceptor(
+            channel,
+            AuthInterceptor {
+                token: token.clone(),
+            },
+        );
         // let registry: registry_service_client::RegistryServiceClient<Channel> = reg.into();
-        Ok(CliRegistryClient::new(reg, user))
+        Ok(CliRegistryClient::new(reg, user, orgs))
     }
 }
 
@@ -134,7 +153,11 @@ pub struct CliRegistryClient {
     registry_client: registry_service_client::RegistryServiceClient<
         InterceptedService<Channel, AuthInterceptor>,
     >,
-    users_client: user_service_client::UserServiceClient<Channel>,
+    users_client:
+        user_service_client::UserServiceClient<InterceptedService<Channel, AuthInterceptor>>,
+    orgs_client: organization_service_client::OrganizationServiceClient<
+        InterceptedService<Channel, AuthInterceptor>,
+    >,
 }
 
 impl CliRegistryClient {
@@ -142,15 +165,21 @@ impl CliRegistryClient {
         registry: registry_service_client::RegistryServiceClient<
             InterceptedService<Channel, AuthInterceptor>,
         >,
-        users: user_service_client::UserServiceClient<Channel>,
+        users: user_service_client::UserServiceClient<InterceptedService<Channel, AuthInterceptor>>,
+        orgs: organization_service_client::OrganizationServiceClient<
+            InterceptedService<Channel, AuthInterceptor>,
+        >,
     ) -> Self {
         Self {
             registry_client: registry,
             users_client: users,
+            orgs_client: orgs,
         }
     }
 
-    pub async fn upload(&mut self, library: Library) -> anyhow::Result<()> {
+    /// Streams the library files to the registry staging area, returning the upload token to
+    /// publish the release with
+    pub async fn upload(&mut self, library: Library) -> anyhow::Result<String> {
         let mut uploads = vec![];
         let mut total_bytes: usize = 0;
 
@@ -195,16 +224,19 @@ impl CliRegistryClient {
         let request = tonic::Request::new(progress_stream);
         // let request = tonic::Request::new(tokio_stream::iter(uploads));
         match self.registry_client.upload(request).await {
-            Ok(_) => {
+            Ok(response) => {
                 pb.finish();
-                Prompter::info("Finished uploading files.")
+                Prompter::info("Finished uploading files.");
+                Ok(response.into_inner().upload_token)
             }
             Err(e) => {
-                pb.finish();
-                Prompter::error(&format!("something went wrong: {:?}", e));
+                pb.abandon();
+                Err(anyhow::anyhow!(
+                    "failed to upload library files: {}",
+                    e.message()
+                ))
             }
         }
-        Ok(())
     }
 
     pub async fn publish(&mut self, publish_req: PublishRequest) -> anyhow::Result<()> {
@@ -225,6 +257,117 @@ impl CliRegistryClient {
         }
     }
 
+    pub async fn add_owner(&mut self, owner_req: OwnerRequest) -> anyhow::Result<()> {
+        self.registry_client
+            .add_owner(owner_req)
+            .await
+            .map_err(|e| anyhow::anyhow!(e.message().to_string()))?;
+        Ok(())
+    }
+
+    pub async fn remove_owner(&mut self, owner_req: OwnerRequest) -> anyhow::Result<()> {
+        self.registry_client
+            .remove_owner(owner_req)
+            .await
+            .map_err(|e| anyhow::anyhow!(e.message().to_string()))?;
+        Ok(())
+    }
+
+    pub async fn list_owners(&mut self, library: &str) -> anyhow::Result<Vec<String>> {
+        let response = self
+            .registry_client
+            .list_owners(ListOwnersRequest {
+                library: library.to_string(),
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e.message().to_string()))?;
+
+        Ok(response.into_inner().owners)
+    }
+
+    pub async fn yank(&mut self, yank_req: YankRequest) -> anyhow::Result<()> {
+        self.registry_client
+            .yank(yank_req)
+            .await
+            .map_err(|e| anyhow::anyhow!(e.message().to_string()))?;
+        Ok(())
+    }
+
+    pub async fn deprecate(&mut self, deprecate_req: DeprecateRequest) -> anyhow::Result<()> {
+        self.registry_client
+            .deprecate(deprecate_req)
+            .await
+            .map_err(|e| anyhow::anyhow!(e.message().to_string()))?;
+        Ok(())
+    }
+
+    pub async fn create_organization(&mut self, name: &str) -> anyhow::Result<Organization> {
+        let response = self
+            .orgs_client
+            .create_organization(CreateOrganizationRequest {
+                name: name.to_string(),
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e.message().to_string()))?;
+
+        Ok(response.into_inner())
+    }
+
+    pub async fn get_organization(&mut self, org_id: &str) -> anyhow::Result<Organization> {
+        let response = self
+            .orgs_client
+            .get_organization(GetOrganizationRequest {
+                org_id: org_id.to_string(),
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e.message().to_string()))?;
+
+        response
+            .into_inner()
+            .organization
+            .ok_or_else(|| anyhow::anyhow!("organization not found: {}", org_id))
+    }
+
+    pub async fn remove_organization(&mut self, org_id: &str) -> anyhow::Result<()> {
+        self.orgs_client
+            .remove_organization(RemoveOrganizationRequest {
+                org_id: org_id.to_string(),
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e.message().to_string()))?;
+        Ok(())
+    }
+
+    pub async fn add_organization_user(&mut self, add_req: AddUserRequest) -> anyhow::Result<()> {
+        self.orgs_client
+            .add_user(add_req)
+            .await
+            .map_err(|e| anyhow::anyhow!(e.message().to_string()))?;
+        Ok(())
+    }
+
+    pub async fn remove_organization_user(
+        &mut self,
+        remove_req: RemoveUserRequest,
+    ) -> anyhow::Result<()> {
+        self.orgs_client
+            .remove_user(remove_req)
+            .await
+            .map_err(|e| anyhow::anyhow!(e.message().to_string()))?;
+        Ok(())
+    }
+
+    pub async fn update_organization_user_role(
+        &mut self,
+        update_req: UpdateUserRoleRequest,
+    ) -> anyhow::Result<()> {
+        self.orgs_client
+            .update_user_role(update_req)
+            .await
+            .map_err(|e| anyhow::anyhow!(e.message().to_string()))?;
+        Ok(())
+    }
+
     // pub async fn download(
     //     &mut self,
     //     download_req: DownloadRequest,
@@ -237,16 +380,15 @@ impl CliRegistryClient {
     //     Ok(response.into_inner())
     // }
 
-    pub async fn create_user(&mut self, login_req: LoginRequest) -> anyhow::Result<plm_core::User> {
-        let create_user = plm_core::CreateUserRequest {
-            password: login_req.token,
-            username: login_req.username,
-        };
+    pub async fn create_user(
+        &mut self,
+        create_req: CreateUserRequest,
+    ) -> anyhow::Result<plm_core::User> {
         let response = self
             .users_client
-            .create_user(create_user)
+            .create_user(create_req)
             .await
-            .map_err(|e| anyhow::anyhow!(e))?;
+            .map_err(|e| anyhow::anyhow!(e.message().to_string()))?;
 
         Ok(response.into_inner())
     }
@@ -255,42 +397,82 @@ impl CliRegistryClient {
         let response = self.users_client.login(login_req).await?;
         Ok(response.into_inner())
     }
+
+    pub async fn create_token(
+        &mut self,
+        create_req: CreateTokenRequest,
+    ) -> anyhow::Result<CreateTokenResponse> {
+        let response = self
+            .users_client
+            .create_token(create_req)
+            .await
+            .map_err(|e| anyhow::anyhow!(e.message().to_string()))?;
+
+        Ok(response.into_inner())
+    }
+
+    pub async fn list_tokens(&mut self) -> anyhow::Result<Vec<ApiToken>> {
+        let response = self
+            .users_client
+            .list_tokens(())
+            .await
+            .map_err(|e| anyhow::anyhow!(e.message().to_string()))?;
+
+        Ok(response.into_inner().tokens)
+    }
+
+    pub async fn revoke_token(&mut self, name: &str) -> anyhow::Result<()> {
+        self.users_client
+            .revoke_token(RevokeTokenRequest {
+                name: name.to_string(),
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e.message().to_string()))?;
+        Ok(())
+    }
 }
 
 #[async_trait]
 impl plm_core::registry::Registry for CliRegistryClient {
+    async fn metadata(&mut self, library: &str) -> anyhow::Result<MetadataResponse> {
+        let response = self
+            .registry_client
+            .metadata(MetadataRequest {
+                library: library.to_string(),
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))?;
+
+        Ok(response.into_inner())
+    }
+
     async fn download(
         &mut self,
         dep: plm_core::plm::library::v1::Dependency,
-    ) -> anyhow::Result<plm_core::plm::library::v1::Library> {
+    ) -> anyhow::Result<plm_core::DownloadResponse> {
+        let name = if dep.version.is_empty() {
+            dep.library_id
+        } else {
+            format!("{}:{}", dep.library_id, dep.version)
+        };
+        let full_or_partial = if dep.packages.is_empty() {
+            plm_core::FullOrPartial::Full(name)
+        } else {
+            plm_core::FullOrPartial::Partial(PartialDownloadRequest {
+                name,
+                packages: dep.packages,
+            })
+        };
         let response = self
             .registry_client
             .download(DownloadRequest {
-                full_or_partial: Some(plm_core::FullOrPartial::Full(if dep.version.eq("") {
-                    dep.library_id
-                } else {
-                    format!("{}:{}", dep.library_id, dep.version)
-                })),
-                ..Default::default()
+                full_or_partial: Some(full_or_partial),
+                compression: Compressions::Gz.into(),
             })
             .await
             .map_err(|e| anyhow::anyhow!(e))?;
 
-        match response.into_inner().protobuf_or_gz {
-            Some(res) => match res {
-                ProtobufOrGz::Gz(_gz) => {
-                    return Err(anyhow::anyhow!(format!(
-                        "error while unpacking download response"
-                    )))
-                }
-                ProtobufOrGz::Protobuf(pb) => Ok(pb),
-            },
-            None => {
-                return Err(anyhow::anyhow!(format!(
-                    "error while unpacking download response"
-                )))
-            }
-        }
+        Ok(response.into_inner())
     }
 
     async fn publish(&self, _lib: plm_core::plm::library::v1::Library) -> anyhow::Result<()> {
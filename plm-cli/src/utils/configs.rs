@@ -146,4 +146,13 @@ impl CliConfigs {
 
         Ok(())
     }
+
+    /// The token to authenticate registry calls with, `PLM_TOKEN` takes precedence over the
+    /// token saved by `plm login` so CI can publish with an API token
+    pub fn auth_token(&self) -> String {
+        match std::env::var("PLM_TOKEN") {
+            Ok(token) if !token.is_empty() => token,
+            _ => self.token.clone().unwrap_or_default(),
+        }
+    }
 }
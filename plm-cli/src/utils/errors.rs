@@ -25,4 +25,13 @@ pub enum PlmError {
 
     #[error("Error on serialization operation: {0:?}")]
     SerializationError(io::Error),
+
+    #[error("Integrity check failed: {0}")]
+    IntegrityError(String),
+
+    #[error("proto-lock.json is out of date: {0}, run `plm install` to update it")]
+    OutdatedLock(String),
+
+    #[error("proto-lock.json is invalid: {0}")]
+    InvalidLock(String),
 }
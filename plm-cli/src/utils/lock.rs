@@ -12,43 +12,96 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use plm_core::FileSystem as fs;
+use plm_core::{
+    utils::{
+        dag::{Dag, Package},
+        hash_content, versioning,
+    },
+    FileSystem as fs,
+};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+};
 
 use super::errors::{PlmError, PlmResult};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Dependency {
     pub name: String,
+    /// The locked version of the dependency
     pub version: String,
+    /// The semver requirement of the dependent, missing from lock files written before it was
+    /// recorded
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub requirement: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Library {
     pub name: String,
+    /// The exact resolved version
     pub version: String,
+    /// The registry the library was downloaded from
+    #[serde(default)]
+    pub source: String,
+    /// SHA-256 digest of the installed files, see [`plm_core::utils::hash_files`]
+    #[serde(default)]
+    pub checksum: String,
+    /// The protobuf packages installed with `--packages`, all of them when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub packages: Vec<String>,
     pub dependencies: Vec<Dependency>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct ProtoLock {
     pub libraries: Vec<Library>,
+    /// SHA-256 digest of the locked libraries, set on every write
+    #[serde(default)]
+    pub integrity_hash: String,
 }
 
 impl ProtoLock {
-    // Read from lock file
+    // Read from lock file, failing on a lock file edited by hand since it was written
     pub fn from_file<P: AsRef<Path>>(path: P) -> PlmResult<Self> {
         let file_content =
             fs::read_file(path.as_ref().to_str().unwrap()).map_err(PlmError::FileSystemError)?;
         let proto_lock: ProtoLock = serde_json::from_str(&file_content)
             .map_err(|err| PlmError::SerializationError(err.into()))?;
+        // Lock files written before integrity hashes were introduced have none
+        if !proto_lock.integrity_hash.is_empty()
+            && proto_lock.integrity_hash != proto_lock.compute_integrity_hash()?
+        {
+            return Err(PlmError::IntegrityError(
+                "proto-lock.json doesn't match its integrity hash".to_string(),
+            ));
+        }
         Ok(proto_lock)
     }
 
-    // Write to lock file
+    // Read from lock file, or start an empty one if there is none yet
+    pub fn from_file_or_default<P: AsRef<Path>>(path: P) -> PlmResult<Self> {
+        if fs::file_exists(path.as_ref().to_str().unwrap()) {
+            Self::from_file(path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    // Write to lock file, libraries sorted by name so the file is stable across installs
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> PlmResult<()> {
-        let file_content = serde_json::to_string_pretty(self)
+        let mut libraries = self.libraries.clone();
+        libraries.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut proto_lock = ProtoLock {
+            libraries,
+            integrity_hash: String::new(),
+        };
+        proto_lock.integrity_hash = proto_lock.compute_integrity_hash()?;
+
+        let file_content = serde_json::to_string_pretty(&proto_lock)
             .map_err(|err| PlmError::SerializationError(err.into()))?;
         fs::write_file(path.as_ref().to_str().unwrap(), &file_content)
             .map_err(PlmError::FileSystemError)?;
@@ -86,13 +139,350 @@ impl ProtoLock {
         }
     }
 
-    // Resolve a library's dependencies recursively
-    pub fn resolve_dependencies(&self, _lib_name: String) -> PlmResult<Vec<Dependency>> {
-        Ok(Vec::new()) // Placeholder
+    // Find the libraries that depend on a given library
+    pub fn dependents(&self, lib_name: &str) -> Vec<&Library> {
+        self.libraries
+            .iter()
+            .filter(|lib| lib.dependencies.iter().any(|dep| dep.name == lib_name))
+            .collect()
+    }
+
+    // Resolve a library's locked dependencies recursively, each one listed once, depth first
+    pub fn resolve_dependencies(&self, lib_name: String) -> PlmResult<Vec<Dependency>> {
+        let lib = self
+            .find_library(Some(lib_name.clone()))
+            .ok_or_else(|| PlmError::InternalError(format!("{} is not locked", lib_name)))?;
+
+        let mut resolved = Vec::new();
+        let mut seen = HashSet::from([lib.name.as_str()]);
+        let mut stack: Vec<&Dependency> = lib.dependencies.iter().rev().collect();
+        while let Some(dependency) = stack.pop() {
+            if !seen.insert(dependency.name.as_str()) {
+                continue;
+            }
+            let locked = self
+                .find_library(Some(dependency.name.clone()))
+                .ok_or_else(|| {
+                    PlmError::InternalError(format!(
+                        "{} depends on {}, which is not locked",
+                        lib_name, dependency.name
+                    ))
+                })?;
+            resolved.push(Dependency {
+                name: locked.name.clone(),
+                version: locked.version.clone(),
+                ..Default::default()
+            });
+            stack.extend(locked.dependencies.iter().rev());
+        }
+        Ok(resolved)
+    }
+
+    // Check the lock file pins every manifest dependency and nothing else, so it can be installed
+    // as is
+    pub fn check_manifest(&self, dependencies: &HashMap<String, String>) -> PlmResult<()> {
+        for (name, requirement) in dependencies {
+            let locked = self
+                .find_library(Some(name.clone()))
+                .ok_or_else(|| PlmError::OutdatedLock(format!("{} is not locked", name)))?;
+            if !versioning::satisfies(requirement, &locked.version) {
+                return Err(PlmError::OutdatedLock(format!(
+                    "{}@{} is locked, but {}@{} is required",
+                    locked.name, locked.version, name, requirement
+                )));
+            }
+            self.resolve_dependencies(name.clone())
+                .map_err(|e| match e {
+                    PlmError::InternalError(msg) => PlmError::OutdatedLock(msg),
+                    e => e,
+                })?;
+        }
+
+        match self.unused_libraries(dependencies).first() {
+            Some(name) => Err(PlmError::OutdatedLock(format!(
+                "{} is locked, but no dependency requires it",
+                name
+            ))),
+            None => Ok(()),
+        }
     }
 
-    // Validate the entire lock file, e.g., for cyclic dependencies
+    // Find the libraries locked although no manifest dependency requires them, directly or not
+    pub fn unused_libraries(&self, dependencies: &HashMap<String, String>) -> Vec<String> {
+        let mut used = HashSet::new();
+        let mut stack: Vec<&str> = dependencies.keys().map(String::as_str).collect();
+        while let Some(name) = stack.pop() {
+            if !used.insert(name) {
+                continue;
+            }
+            if let Some(lib) = self.find_library(Some(name.to_string())) {
+                stack.extend(lib.dependencies.iter().map(|dep| dep.name.as_str()));
+            }
+        }
+
+        self.libraries
+            .iter()
+            .filter(|lib| !used.contains(lib.name.as_str()))
+            .map(|lib| lib.name.clone())
+            .collect()
+    }
+
+    // Remove the libraries no manifest dependency requires anymore, returning their names
+    pub fn prune(&mut self, dependencies: &HashMap<String, String>) -> Vec<String> {
+        let unused = self.unused_libraries(dependencies);
+        self.libraries.retain(|lib| !unused.contains(&lib.name));
+        unused
+    }
+
+    // Digest of the libraries, as serialized in the lock file
+    fn compute_integrity_hash(&self) -> PlmResult<String> {
+        let content = serde_json::to_vec(&self.libraries)
+            .map_err(|err| PlmError::SerializationError(err.into()))?;
+        Ok(hash_content(&content))
+    }
+
+    // Validate the entire lock file as a dependency graph: every dependency must be locked, at a
+    // version the requirements of all of its dependents accept, without cycles
     pub fn validate(&self) -> PlmResult<()> {
-        Ok(()) // Placeholder
+        let mut problems = vec![];
+        let locked_ref = |name: &str| match self.find_library(Some(name.to_string())) {
+            Some(lib) => format!("{}@{}", lib.name, lib.version),
+            None => name.to_string(),
+        };
+
+        // Inserted as is rather than with `Dag::add_package`, so cycles are reported with their
+        // chain by `Dag::find_cycle`
+        let mut dag = Dag::new();
+        for lib in &self.libraries {
+            let package = Package {
+                name: lib.name.clone(),
+                dependencies: lib.dependencies.iter().map(|d| d.name.clone()).collect(),
+            };
+            if dag.nodes.insert(lib.name.clone(), package).is_some() {
+                problems.push(format!("{} is locked more than once", lib.name));
+            }
+        }
+
+        if let Some(cycle) = dag.find_cycle() {
+            let chain: Vec<String> = cycle.iter().map(|name| locked_ref(name)).collect();
+            problems.push(format!("dependency cycle {}", chain.join(" -> ")));
+        }
+
+        for (dependent, dependency) in dag.missing_dependencies() {
+            problems.push(format!(
+                "{} depends on {}, which is not locked",
+                locked_ref(&dependent),
+                dependency
+            ));
+        }
+
+        // Dependents whose requirement rejects the locked version, by dependency. Lock files
+        // written before requirements were recorded require the exact version instead
+        let mut unsatisfied: BTreeMap<&str, (&Library, Vec<String>)> = BTreeMap::new();
+        for lib in &self.libraries {
+            for dep in &lib.dependencies {
+                let Some(locked) = self.find_library(Some(dep.name.clone())) else {
+                    continue;
+                };
+                let (required, accepted) = if dep.requirement.is_empty() {
+                    (&dep.version, dep.version == locked.version)
+                } else {
+                    (
+                        &dep.requirement,
+                        versioning::satisfies(&dep.requirement, &locked.version),
+                    )
+                };
+                if !accepted {
+                    unsatisfied
+                        .entry(dep.name.as_str())
+                        .or_insert_with(|| (locked, vec![]))
+                        .1
+                        .push(format!(
+                            "{}@{} -> {}@{}",
+                            lib.name, lib.version, dep.name, required
+                        ));
+                }
+            }
+        }
+        for (name, (locked, chains)) in unsatisfied {
+            problems.push(format!(
+                "{}, but {}@{} is locked",
+                chains.join(", "),
+                name,
+                locked.version
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(PlmError::InvalidLock(problems.join("; ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library(name: &str, version: &str, dependencies: &[&str]) -> Library {
+        Library {
+            name: name.to_string(),
+            version: version.to_string(),
+            source: "http://127.0.0.1:7575".to_string(),
+            checksum: String::new(),
+            packages: vec![],
+            // `name@version`, followed by the requirement of the dependent if any
+            dependencies: dependencies
+                .iter()
+                .map(|dep| {
+                    let (dep, requirement) = dep.split_once(' ').unwrap_or((dep, ""));
+                    let (name, version) = dep.split_once('@').unwrap();
+                    Dependency {
+                        name: name.to_string(),
+                        version: version.to_string(),
+                        requirement: requirement.to_string(),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_dependencies() {
+        let proto_lock = ProtoLock {
+            libraries: vec![
+                library("a", "2.0.0", &["b@1.0.0", "d@0.1.0"]),
+                library("b", "1.0.0", &["c@0.1.0"]),
+                library("c", "0.1.0", &[]),
+                library("d", "0.1.0", &["c@0.1.0"]),
+            ],
+            ..Default::default()
+        };
+
+        let resolved: Vec<String> = proto_lock
+            .resolve_dependencies("a".to_string())
+            .unwrap()
+            .into_iter()
+            .map(|dep| format!("{}@{}", dep.name, dep.version))
+            .collect();
+        assert_eq!(resolved, ["b@1.0.0", "c@0.1.0", "d@0.1.0"]);
+        assert!(proto_lock.resolve_dependencies("e".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut proto_lock = ProtoLock {
+            libraries: vec![
+                library("a", "2.0.0", &["b@1.0.0"]),
+                library("b", "1.0.0", &["c@0.1.0"]),
+                library("c", "0.1.0", &[]),
+            ],
+            ..Default::default()
+        };
+        assert!(proto_lock.validate().is_ok());
+
+        let invalid = |proto_lock: &ProtoLock| match proto_lock.validate() {
+            Err(PlmError::InvalidLock(problems)) => problems,
+            other => panic!("expected an invalid lock, got {:?}", other),
+        };
+
+        proto_lock.libraries[2] = library("c", "0.1.0", &["a@2.0.0"]);
+        assert_eq!(
+            invalid(&proto_lock),
+            "dependency cycle a@2.0.0 -> b@1.0.0 -> c@0.1.0 -> a@2.0.0"
+        );
+
+        proto_lock.libraries[2] = library("c", "0.2.0", &["d@0.1.0"]);
+        proto_lock
+            .libraries
+            .push(library("e", "1.0.0", &["c@0.2.0"]));
+        assert_eq!(
+            invalid(&proto_lock),
+            "c@0.2.0 depends on d, which is not locked; b@1.0.0 -> c@0.1.0, but c@0.2.0 is locked"
+        );
+
+        proto_lock.libraries.truncate(2);
+        assert_eq!(
+            invalid(&proto_lock),
+            "b@1.0.0 depends on c, which is not locked"
+        );
+    }
+
+    #[test]
+    fn test_validate_requirements() {
+        // A shared dependency bumped by one dependent is still accepted by the other one
+        let mut proto_lock = ProtoLock {
+            libraries: vec![
+                library("a", "2.0.0", &["b@1.0.0 ^1.0.0", "c@0.1.1 ^0.1.1"]),
+                library("b", "1.0.0", &["c@0.1.0 ^0.1.0"]),
+                library("c", "0.1.1", &[]),
+            ],
+            ..Default::default()
+        };
+        assert!(proto_lock.validate().is_ok());
+
+        proto_lock.libraries[0] = library("a", "3.0.0", &["b@1.0.0 ^1.0.0", "c@0.2.0 ^0.2.0"]);
+        proto_lock.libraries[2] = library("c", "0.2.0", &[]);
+        assert!(matches!(
+            proto_lock.validate(),
+            Err(PlmError::InvalidLock(problems))
+                if problems == "b@1.0.0 -> c@^0.1.0, but c@0.2.0 is locked"
+        ));
+    }
+
+    #[test]
+    fn test_check_manifest() {
+        let proto_lock = ProtoLock {
+            libraries: vec![
+                library("b", "1.0.0", &["c@0.1.0"]),
+                library("c", "0.1.0", &[]),
+            ],
+            ..Default::default()
+        };
+        let manifest = |deps: &[(&str, &str)]| -> HashMap<String, String> {
+            deps.iter()
+                .map(|(name, req)| (name.to_string(), req.to_string()))
+                .collect()
+        };
+
+        assert!(proto_lock.check_manifest(&manifest(&[("b", "^1")])).is_ok());
+        assert!(proto_lock
+            .unused_libraries(&manifest(&[("b", "^1")]))
+            .is_empty());
+        assert_eq!(proto_lock.unused_libraries(&manifest(&[("c", "")])), ["b"]);
+        // Outdated requirement, missing library and stale entry
+        for deps in [&[("b", "^2")][..], &[("b", "^1"), ("d", "")], &[("c", "")]] {
+            assert!(matches!(
+                proto_lock.check_manifest(&manifest(deps)),
+                Err(PlmError::OutdatedLock(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_integrity_hash() {
+        let path = std::env::temp_dir().join(format!("plm-lock-{}.json", std::process::id()));
+        let proto_lock = ProtoLock {
+            libraries: vec![
+                library("b", "1.0.0", &["c@0.1.0"]),
+                library("c", "0.1.0", &[]),
+            ],
+            ..Default::default()
+        };
+        proto_lock.to_file(&path).unwrap();
+
+        let read = ProtoLock::from_file(&path).unwrap();
+        assert_eq!(read.integrity_hash, read.compute_integrity_hash().unwrap());
+
+        let tampered = std::fs::read_to_string(&path)
+            .unwrap()
+            .replace("1.0.0", "1.0.1");
+        std::fs::write(&path, tampered).unwrap();
+        assert!(matches!(
+            ProtoLock::from_file(&path),
+            Err(PlmError::IntegrityError(_))
+        ));
+        std::fs::remove_file(path).ok();
     }
 }
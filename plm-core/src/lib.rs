@@ -54,9 +54,11 @@ pub mod protoc {
 /// A collection of helpers that shared to multiple logics
 pub mod utils {
     use sha2::{Digest, Sha256};
+    pub mod archive;
     pub mod auth;
     pub mod dag;
     pub mod fs;
+    pub mod protos;
     pub mod versioning;
     pub fn hash_fd_set(fd_set_bytes: Vec<u8>) -> String {
         let mut hasher = Sha256::new();
@@ -65,6 +67,29 @@ pub mod utils {
 
         hex::encode(hash)
     }
+
+    /// Hex encoded SHA-256 digest of a file content
+    pub fn hash_content(content: &[u8]) -> String {
+        hex::encode(Sha256::digest(content))
+    }
+
+    /// Hex encoded SHA-256 digest of a set of files, regardless of their order: every file is
+    /// hashed as a `<name>\0<content digest>\n` line, sorted by name
+    pub fn hash_files<'a>(
+        files: impl IntoIterator<Item = &'a crate::plm::package::v1::File>,
+    ) -> String {
+        let mut lines: Vec<String> = files
+            .into_iter()
+            .map(|f| format!("{}\0{}\n", f.name, hash_content(&f.content)))
+            .collect();
+        lines.sort();
+
+        let mut hasher = Sha256::new();
+        for line in lines {
+            hasher.update(line.as_bytes());
+        }
+        hex::encode(hasher.finalize())
+    }
 }
 
 pub mod library {
@@ -80,10 +105,14 @@ pub mod registry {
 
     #[async_trait]
     pub trait Registry {
+        async fn metadata(
+            &mut self,
+            library: &str,
+        ) -> anyhow::Result<plm::registry::v1::MetadataResponse>;
         async fn download(
             &mut self,
             dep: plm::library::v1::Dependency,
-        ) -> anyhow::Result<plm::library::v1::Library>;
+        ) -> anyhow::Result<plm::registry::v1::DownloadResponse>;
         async fn publish(&self, lib: plm::library::v1::Library) -> anyhow::Result<()>;
     }
 }
@@ -158,8 +187,13 @@ impl serde::Serialize for plm::registry::v1::S3 {
     where
         S: Serializer,
     {
-        let mut s = serializer.serialize_struct("S3", 1)?;
+        let mut s = serializer.serialize_struct("S3", 6)?;
         s.serialize_field("bucketName", &self.bucket_name)?;
+        s.serialize_field("endpoint", &self.endpoint)?;
+        s.serialize_field("region", &self.region)?;
+        s.serialize_field("accessKey", &self.access_key)?;
+        s.serialize_field("secretKey", &self.secret_key)?;
+        s.serialize_field("pathStyle", &self.path_style)?;
         s.end()
     }
 }
@@ -200,6 +234,26 @@ impl serde::Serialize for plm::registry::v1::Data {
     }
 }
 
+impl serde::Serialize for plm::registry::v1::Auth {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("Auth", 10)?;
+        s.serialize_field("algorithm", self.algorithm().as_str_name())?;
+        s.serialize_field("secretFile", &self.secret_file)?;
+        s.serialize_field("privateKeyFile", &self.private_key_file)?;
+        s.serialize_field("publicKeyFile", &self.public_key_file)?;
+        s.serialize_field("tokenExpiry", &self.token_expiry)?;
+        s.serialize_field("issuer", &self.issuer)?;
+        s.serialize_field("audience", &self.audience)?;
+        s.serialize_field("devMode", &self.dev_mode)?;
+        s.serialize_field("disableSignup", &self.disable_signup)?;
+        s.serialize_field("admins", &self.admins)?;
+        s.end()
+    }
+}
+
 impl serde::Serialize for plm::registry::v1::storage::StorageBackend {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -241,6 +295,7 @@ impl<'de> serde::Deserialize<'de> for plm::registry::v1::Config {
                 let mut storage: Option<plm::registry::v1::Storage> = None;
                 let mut server: Option<plm::registry::v1::Server> = None;
                 let mut data: Option<plm::registry::v1::Data> = None;
+                let mut auth: Option<plm::registry::v1::Auth> = None;
 
                 while let Some(key) = map.next_key()? {
                     match key {
@@ -262,6 +317,12 @@ impl<'de> serde::Deserialize<'de> for plm::registry::v1::Config {
                             }
                             data = Some(map.next_value()?);
                         }
+                        "auth" => {
+                            if auth.is_some() {
+                                return Err(serde::de::Error::duplicate_field("auth"));
+                            }
+                            auth = Some(map.next_value()?);
+                        }
                         _ => {
                             // Unknown field, you can decide how to handle this
                             let _ = map.next_value::<serde::de::IgnoredAny>()?;
@@ -273,11 +334,16 @@ impl<'de> serde::Deserialize<'de> for plm::registry::v1::Config {
                     storage,
                     server,
                     data,
+                    auth,
                 })
             }
         }
 
-        deserializer.deserialize_struct("Config", &["storage", "server", "data"], ConfigVisitor)
+        deserializer.deserialize_struct(
+            "Config",
+            &["storage", "server", "data", "auth"],
+            ConfigVisitor,
+        )
     }
 }
 
@@ -357,26 +423,40 @@ impl<'de> Deserialize<'de> for S3 {
             type Value = S3;
 
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("struct S3 with field bucket_name")
+                formatter
+                    .write_str("struct S3 with field bucket_name and optional connection fields")
             }
 
             fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
             where
                 A: MapAccess<'de>,
             {
+                const FIELDS: &[&str] = &[
+                    "bucketName",
+                    "endpoint",
+                    "region",
+                    "accessKey",
+                    "secretKey",
+                    "pathStyle",
+                ];
                 let mut bucket_name = None;
+                let mut s3 = S3::default();
 
                 while let Some(key) = map.next_key::<String>()? {
-                    if key == "bucketName" {
-                        bucket_name = Some(map.next_value()?);
-                    } else {
-                        return Err(de::Error::unknown_field(&key, &["bucketName"]));
+                    match key.as_str() {
+                        "bucketName" => bucket_name = Some(map.next_value()?),
+                        "endpoint" => s3.endpoint = map.next_value()?,
+                        "region" => s3.region = map.next_value()?,
+                        "accessKey" => s3.access_key = map.next_value()?,
+                        "secretKey" => s3.secret_key = map.next_value()?,
+                        "pathStyle" => s3.path_style = map.next_value()?,
+                        _ => return Err(de::Error::unknown_field(&key, FIELDS)),
                     }
                 }
 
-                let bucket_name =
+                s3.bucket_name =
                     bucket_name.ok_or_else(|| de::Error::missing_field("bucketName"))?;
-                Ok(S3 { bucket_name })
+                Ok(s3)
             }
         }
 
@@ -384,6 +464,71 @@ impl<'de> Deserialize<'de> for S3 {
     }
 }
 
+impl<'de> Deserialize<'de> for Auth {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct AuthVisitor;
+
+        impl<'de> Visitor<'de> for AuthVisitor {
+            type Value = Auth;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("struct Auth with optional signing and claims fields")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                const FIELDS: &[&str] = &[
+                    "algorithm",
+                    "secretFile",
+                    "privateKeyFile",
+                    "publicKeyFile",
+                    "tokenExpiry",
+                    "issuer",
+                    "audience",
+                    "devMode",
+                    "disableSignup",
+                    "admins",
+                ];
+                let mut auth = Auth::default();
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "algorithm" => {
+                            let name = map.next_value::<String>()?;
+                            let algorithm = plm::registry::v1::auth::Algorithm::from_str_name(
+                                &name.to_uppercase(),
+                            )
+                            .ok_or_else(|| {
+                                de::Error::unknown_variant(&name, &["HS256", "RS256", "EdDSA"])
+                            })?;
+                            auth.set_algorithm(algorithm);
+                        }
+                        "secretFile" => auth.secret_file = map.next_value()?,
+                        "privateKeyFile" => auth.private_key_file = map.next_value()?,
+                        "publicKeyFile" => auth.public_key_file = map.next_value()?,
+                        "tokenExpiry" => auth.token_expiry = map.next_value()?,
+                        "issuer" => auth.issuer = map.next_value()?,
+                        "audience" => auth.audience = map.next_value()?,
+                        "devMode" => auth.dev_mode = map.next_value()?,
+                        "disableSignup" => auth.disable_signup = map.next_value()?,
+                        "admins" => auth.admins = map.next_value()?,
+                        _ => return Err(de::Error::unknown_field(&key, FIELDS)),
+                    }
+                }
+
+                Ok(auth)
+            }
+        }
+
+        deserializer.deserialize_map(AuthVisitor)
+    }
+}
+
 impl<'de> Deserialize<'de> for Local {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -560,13 +705,15 @@ pub use crate::{
         package::v1::{LockFile, LockedDependency, Manifest, Package},
         registry::v1::{
             download_request::FullOrPartial, download_response::ProtobufOrGz,
-            registry_service_client, registry_service_server, Compressions, Config, Data,
-            DownloadRequest, DownloadResponse, Local, PartialDownloadRequest, PublishRequest,
-            Server, Storage, S3,
+            registry_service_client, registry_service_server, Access, Auth, Compressions, Config,
+            Data, DeprecateRequest, Deprecation, DownloadRequest, DownloadResponse,
+            ListOwnersRequest, ListOwnersResponse, Local, MetadataRequest, MetadataResponse,
+            OwnerRequest, PartialDownloadRequest, PublishRequest, Server, Storage, YankRequest, S3,
         },
         user::v1::{
-            user_service_client, user_service_server, CreateUserRequest, LoginRequest,
-            LoginResponse, Role, User,
+            user_service_client, user_service_server, ApiToken, CreateTokenRequest,
+            CreateTokenResponse, CreateUserRequest, ListTokensResponse, LoginRequest,
+            LoginResponse, RevokeTokenRequest, Role, User,
         },
     },
     utils::fs::FileSystem,
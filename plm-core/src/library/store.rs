@@ -21,7 +21,7 @@ use std::{
 use anyhow::{anyhow, Context, Result};
 use protobuf::{descriptor::FileDescriptorSet, Message};
 use tokio::fs;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::{
     manifest::MANIFEST_FILE,
@@ -29,7 +29,8 @@ use crate::{
         library::v1::{Dependency, Library},
         package::v1::File,
     },
-    FileSystem, Manifest,
+    utils::{archive, hash_files, versioning},
+    DownloadResponse, FileSystem, Manifest, ProtobufOrGz,
 };
 
 /// IO abstraction layer over local `plm` library store
@@ -63,12 +64,53 @@ impl LibraryStore {
             .with_context(|| "Failed to uninstall dependencies")
     }
 
-    /// Unpacks a library into a local directory
-    pub async fn unpack(library: &Library) -> Result<()> {
-        let lib_dir = Path::new(Self::PROTO_MODULES_PATH).join(library.name.as_str());
+    /// Decodes a downloaded library, plain or gzipped
+    pub fn decode(response: DownloadResponse) -> Result<Library> {
+        match response.protobuf_or_gz {
+            Some(ProtobufOrGz::Protobuf(library)) => Ok(library),
+            Some(ProtobufOrGz::Gz(gz)) => archive::unpack_library(&gz),
+            None => Err(anyhow!("error while unpacking download response")),
+        }
+    }
+
+    /// Unpacks a downloaded library, plain or gzipped, into a local directory
+    pub async fn unpack(response: DownloadResponse) -> Result<Library> {
+        Self::write(Self::decode(response)?).await
+    }
+
+    /// Writes a library into its local directory
+    pub async fn write(library: Library) -> Result<Library> {
+        Self::write_to(Path::new(Self::PROTO_MODULES_PATH), library).await
+    }
+
+    /// Writes a library into its directory under `root`, replacing whatever release was there
+    async fn write_to(root: &Path, library: Library) -> Result<Library> {
+        // Names come from the registry, so none of them may point outside of the library directory
+        let name = Path::new(&library.name);
+        if !archive::is_relative_path(name) || name.file_name().is_none() {
+            return Err(anyhow!("Invalid library name {}", library.name));
+        }
+        for file in library.packages.iter().flat_map(|pkg| &pkg.files) {
+            if !archive::is_relative_path(Path::new(&file.name)) {
+                return Err(anyhow!(
+                    "{}@{} contains an invalid file path {}",
+                    library.name,
+                    library.version,
+                    file.name
+                ));
+            }
+        }
+
+        let lib_dir = root.join(library.name.as_str());
         // .join(library.version.as_str());
 
-        // fs::remove_dir_all(&lib_dir).await.ok();
+        // Files dropped by this release must not linger from the previously installed one
+        if lib_dir.exists() {
+            fs::remove_dir_all(&lib_dir)
+                .await
+                .with_context(|| format!("Failed to clear {}", lib_dir.display()))?;
+            debug!("cleared library directory: {:?}", lib_dir);
+        }
 
         fs::create_dir_all(&lib_dir)
             .await
@@ -77,11 +119,6 @@ impl LibraryStore {
         // library
 
         for pkg in library.clone().packages {
-            if !pkg.name.eq("") {
-                let pkg_dir = Path::new(&lib_dir).join(pkg.name.as_str());
-                fs::remove_dir_all(&pkg_dir).await.ok();
-                debug!("clearing package directory: {:?}", pkg_dir);
-            }
             for file in pkg.files {
                 let file_path = Path::new(&lib_dir).join(file.name);
                 fs::create_dir_all(file_path.parent().unwrap()).await?;
@@ -93,7 +130,7 @@ impl LibraryStore {
         let manifest = crate::Manifest {
             name: library.clone().name,
             version: library.clone().version,
-            dependencies: HashMap::new(),
+            dependencies: library.clone().dependencies,
             ..Default::default()
         };
         let manifest_path = FileSystem::join_paths(lib_dir.clone(), "proto-package.json");
@@ -107,77 +144,320 @@ impl LibraryStore {
             lib_dir.display()
         );
 
-        Ok(())
+        Ok(library)
     }
 
-    /// Installs a package and all of its dependency into the local filesystem
+    /// Installs a library and all of its transitive dependencies into the local filesystem.
+    ///
+    /// Returns every installed library, the requested one first. Only the requested library is
+    /// narrowed down to `dependency.packages`, its dependencies are always installed whole.
     pub async fn install<R: crate::registry::Registry>(
         dependency: Dependency,
         registry: &mut R,
-    ) -> Result<Library> {
-        let library = registry.download(dependency).await?;
-
-        debug!("downloaded: {}:{}", library.name, library.version);
-        let binding = library.clone();
-        Self::unpack(&library).await?;
-
-        let tree = format!(":: installed {}@{}", library.name, library.version);
-
-        let Manifest { dependencies, .. } = Self::resolve(&Dependency {
-            library_id: library.name,
-            version: library.version,
-        })
-        .await?;
-
-        let _dependency_count = dependencies.len();
-
-        for (index, dependency) in dependencies.into_iter().enumerate() {
-            info!("i: {} - {:?}", index, dependency);
-            // if let Ok(manifest) = Self::resolve(&dependency.package).await {
-            // let existing = manifest.package.wrap_err(eyre::eyre!(
-            //     "Found installed manifest for {} but it is malformed",
-            //     dependency.package,
-            // ))?;
-
-            // eyre::ensure!(
-            //     dependency.manifest.version.matches(&existing.version),
-            //     "A dependency of your project requires {}@{} which collides with {}@{} required by {}",
-            //     existing.name,
-            //     existing.version,
-            //     dependency.package,
-            //     dependency.manifest.version,
-            //     package.manifest.name,
-            // );
-            // }
-
-            // let dependency = registry.download(dependency).await?;
-
-            // Self::unpack(&dependency).await?;
-
-            // let tree_char = if index + 1 == dependency_count {
-            //     '┗'
-            // } else {
-            //     '┣'
-            // };
-
-            // tree.push_str(&format!(
-            //     "\n   {tree_char} installed {}@{}",
-            //     dependency.manifest.name, dependency.manifest.version
-            // ));
+    ) -> Result<Vec<Library>> {
+        let mut installed = vec![];
+        for library in Self::fetch(dependency, registry).await? {
+            installed.push(Self::write(library).await?);
+        }
+        Ok(installed)
+    }
+
+    /// Resolves and downloads a library and all of its transitive dependencies, without writing
+    /// them to the local filesystem, see [`LibraryStore::install`].
+    pub async fn fetch<R: crate::registry::Registry>(
+        dependency: Dependency,
+        registry: &mut R,
+    ) -> Result<Vec<Library>> {
+        Self::fetch_with(dependency, &[], registry).await
+    }
+
+    /// Same as [`LibraryStore::fetch`], except libraries already in `resolved`, fetched whole for
+    /// another dependency, are reused instead of being resolved and downloaded again.
+    pub async fn fetch_with<R: crate::registry::Registry>(
+        dependency: Dependency,
+        resolved: &[Library],
+        registry: &mut R,
+    ) -> Result<Vec<Library>> {
+        let mut fetched: Vec<Library> = vec![];
+        let mut tree = String::new();
+
+        // Depth-first walk over the dependency graph, where each dependency version is a semver
+        // requirement: (dependency, tree prefix, is last child, depth)
+        let mut stack = vec![(dependency, String::new(), true, 0)];
+        while let Some((dependency, prefix, last, depth)) = stack.pop() {
+            let tree_char = if last { '┗' } else { '┣' };
+
+            if let Some(existing) = fetched.iter().find(|lib| lib.name == dependency.library_id) {
+                if !versioning::satisfies(&dependency.version, &existing.version) {
+                    warn!(
+                        "{}@{} is required, but {}@{} is already resolved",
+                        dependency.library_id, dependency.version, existing.name, existing.version
+                    );
+                }
+                tree.push_str(&format!(
+                    "\n   {prefix}{tree_char} {}@{} (deduped)",
+                    existing.name, existing.version
+                ));
+                continue;
+            }
+
+            // Narrowed down libraries are never shared, as they miss some of their packages
+            let reused = resolved
+                .iter()
+                .find(|lib| dependency.packages.is_empty() && lib.name == dependency.library_id);
+            let library = match reused {
+                Some(library) => {
+                    if !versioning::satisfies(&dependency.version, &library.version) {
+                        warn!(
+                            "{}@{} is required, but {}@{} is already resolved",
+                            dependency.library_id,
+                            dependency.version,
+                            library.name,
+                            library.version
+                        );
+                    }
+                    debug!("reused: {}:{}", library.name, library.version);
+                    library.clone()
+                }
+                None => {
+                    let version = Self::resolve_version(&dependency, registry).await?;
+                    let response = registry
+                        .download(Dependency {
+                            library_id: dependency.library_id,
+                            version,
+                            packages: dependency.packages,
+                        })
+                        .await?;
+                    let checksum = response.checksum.clone();
+                    let library = Self::decode(response)?;
+                    Self::verify_checksum(&library, &checksum)?;
+                    debug!("downloaded: {}:{}", library.name, library.version);
+                    library
+                }
+            };
+
+            let child_prefix = if depth == 0 {
+                tree.push_str(&format!(":: resolved {}@{}", library.name, library.version));
+                String::new()
+            } else {
+                tree.push_str(&format!(
+                    "\n   {prefix}{tree_char} resolved {}@{}",
+                    library.name, library.version
+                ));
+                format!("{prefix}{}", if last { "   " } else { "┃  " })
+            };
+
+            // Push in reverse so dependencies are visited in name order
+            let mut dependencies: Vec<(&String, &String)> = library.dependencies.iter().collect();
+            dependencies.sort();
+            for (index, (name, version)) in dependencies.into_iter().enumerate().rev() {
+                stack.push((
+                    Dependency {
+                        library_id: name.clone(),
+                        version: version.clone(),
+                        ..Default::default()
+                    },
+                    child_prefix.clone(),
+                    index + 1 == library.dependencies.len(),
+                    depth + 1,
+                ));
+            }
+
+            fetched.push(library);
         }
 
         info!("{tree}");
-        Ok(binding)
+        Ok(fetched)
+    }
+
+    /// Installs a single library at an exact version, as pinned by a lock file, without its
+    /// dependencies.
+    ///
+    /// Unlike [`LibraryStore::install`] the version is not resolved, so yanked releases are still
+    /// installed. The library is only written once its files match `checksum`, unless it's empty.
+    pub async fn install_locked<R: crate::registry::Registry>(
+        dependency: Dependency,
+        checksum: &str,
+        registry: &mut R,
+    ) -> Result<Library> {
+        let response = registry.download(dependency.clone()).await?;
+        let downloaded = response.checksum.clone();
+        let library = Self::decode(response)?;
+        if library.version != dependency.version {
+            return Err(anyhow!(
+                "registry returned {}@{} instead of the locked {}@{}",
+                library.name,
+                library.version,
+                dependency.library_id,
+                dependency.version
+            ));
+        }
+
+        Self::verify_checksum(&library, &downloaded)?;
+        Self::verify_checksum(&library, checksum)
+            .with_context(|| "the release changed since it was locked")?;
+        Self::write(library).await
+    }
+
+    /// Checks the files of a library against a checksum, an empty checksum is not checked
+    pub fn verify_checksum(library: &Library, checksum: &str) -> Result<()> {
+        let actual = hash_files(library.packages.iter().flat_map(|pkg| &pkg.files));
+        if !checksum.is_empty() && actual != checksum {
+            return Err(anyhow!(
+                "checksum mismatch for {}@{}: expected {}, got {}",
+                library.name,
+                library.version,
+                checksum,
+                actual
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checksum of the files of an installed library, the same as the checksum of the files it
+    /// was installed from
+    pub fn checksum_installed(library: &Dependency) -> Result<String> {
+        let lib_dir = Self::locate(library);
+        if !lib_dir.is_dir() {
+            return Err(anyhow!("{} is not installed", library.library_id));
+        }
+        Self::checksum_dir(&lib_dir)
+    }
+
+    fn checksum_dir(lib_dir: &Path) -> Result<String> {
+        let mut files = vec![];
+        let mut dirs = vec![lib_dir.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                let name = path.strip_prefix(lib_dir)?.to_string_lossy().to_string();
+                // Written by `LibraryStore::write`, not part of the library files
+                if name == MANIFEST_FILE {
+                    continue;
+                }
+                files.push(File {
+                    content: std::fs::read(&path)?,
+                    name,
+                });
+            }
+        }
+        Ok(hash_files(&files))
     }
 
-    /// Uninstalls a package from the local file system
-    // pub async fn uninstall(package: &PackageId) -> eyre::Result<()> {
-    //     let pkg_dir = Path::new(Self::PROTO_VENDOR_PATH).join(package.as_str());
+    /// Resolves a dependency version requirement against the releases available on the registry.
+    ///
+    /// An empty requirement matches any release, so the latest one is picked. Yanked releases
+    /// are never picked.
+    pub async fn resolve_version<R: crate::registry::Registry>(
+        dependency: &Dependency,
+        registry: &mut R,
+    ) -> Result<String> {
+        let metadata = registry.metadata(&dependency.library_id).await?;
+        let requirement = if dependency.version.is_empty() {
+            "*".to_string()
+        } else {
+            dependency.version.clone()
+        };
+
+        let (yanked, available): (Vec<_>, Vec<_>) =
+            metadata.versions.into_values().partition(|v| v.yanked);
+        let available = HashMap::from([(
+            dependency.library_id.clone(),
+            available.into_iter().map(|v| v.version).collect(),
+        )]);
+        let requirements = HashMap::from([(dependency.library_id.clone(), requirement.clone())]);
+        let mut resolved = versioning::resolve_versions(available, requirements).map_err(|e| {
+            let yanked_matches = yanked
+                .iter()
+                .any(|v| versioning::satisfies(&requirement, &v.version));
+            anyhow!(
+                "failed to resolve {}@{}: {}{}",
+                dependency.library_id,
+                requirement,
+                e,
+                if yanked_matches {
+                    ", the matching releases are yanked"
+                } else {
+                    ""
+                }
+            )
+        })?;
+
+        let version = resolved.remove(&dependency.library_id).unwrap();
+        debug!(
+            "resolved {}@{} -> {}",
+            dependency.library_id, requirement, version
+        );
+        Ok(version)
+    }
 
-    //     fs::remove_dir_all(&pkg_dir)
-    //         .await
-    //         .wrap_err_with(|| format!("Failed to uninstall {package}"))
-    // }
+    /// Uninstalls a library from the local file system
+    pub async fn uninstall(library: &Dependency) -> Result<()> {
+        let lib_dir = Self::locate(library);
+
+        fs::remove_dir_all(&lib_dir)
+            .await
+            .with_context(|| format!("Failed to uninstall {}", library.library_id))?;
+
+        // Scoped libraries live under `proto_modules/@org/`, drop the scope dir once empty
+        if let Some(scope_dir) = lib_dir.parent() {
+            if scope_dir != Path::new(Self::PROTO_MODULES_PATH)
+                && FileSystem::list_dir(scope_dir.to_str().unwrap())?.is_empty()
+            {
+                fs::remove_dir(scope_dir).await.ok();
+            }
+        }
+
+        debug!(
+            "uninstalled {} from {}",
+            library.library_id,
+            lib_dir.display()
+        );
+
+        Ok(())
+    }
+
+    /// Lists the installed libraries whose manifest depends on the given library
+    pub async fn dependents(lib_name: &str) -> Result<Vec<Manifest>> {
+        let mut dependents = vec![];
+        if !FileSystem::dir_exists(Self::PROTO_MODULES_PATH) {
+            return Ok(dependents);
+        }
+
+        let mut lib_dirs = vec![];
+        for entry in FileSystem::list_dir(Self::PROTO_MODULES_PATH)? {
+            let path = PathBuf::from(entry);
+            let is_scope = path
+                .file_name()
+                .map(|n| n.to_string_lossy().starts_with('@'))
+                .unwrap_or(false);
+            if is_scope && path.is_dir() {
+                for scoped in FileSystem::list_dir(path.to_str().unwrap())? {
+                    lib_dirs.push(PathBuf::from(scoped));
+                }
+            } else if path.is_dir() {
+                lib_dirs.push(path);
+            }
+        }
+
+        for lib_dir in lib_dirs {
+            let manifest_path = lib_dir.join(MANIFEST_FILE);
+            if !manifest_path.is_file() {
+                continue;
+            }
+            let manifest = FileSystem::read_manifest(manifest_path.to_str().unwrap())
+                .with_context(|| format!("Failed to read local manifest: {:?}", manifest_path))?;
+            if manifest.name != lib_name && manifest.dependencies.contains_key(lib_name) {
+                dependents.push(manifest);
+            }
+        }
+
+        Ok(dependents)
+    }
 
     /// Resolves a package in the local file system
     pub async fn resolve(lib: &Dependency) -> Result<Manifest> {
@@ -197,6 +477,7 @@ impl LibraryStore {
             let _resolved = Self::resolve(&Dependency {
                 library_id: dependency.0.to_string(),
                 version: dependency.1.to_string(),
+                ..Default::default()
             })
             .await
             .with_context(|| "Failed to resolve dependency locally")?;
@@ -238,7 +519,7 @@ impl LibraryStore {
             // Compile the proto files using `tonic_build`
             match preserve_imports {
                 true => {
-                    for (_, dep) in manifest.clone().dependencies.into_iter().enumerate() {
+                    for dep in manifest.clone().dependencies.into_iter() {
                         let dep_path =
                             FileSystem::join_paths(Self::PROTO_MODULES_PATH, dep.0.clone());
                         include_paths.push(dep_path.to_str().unwrap().to_string());
@@ -259,7 +540,7 @@ impl LibraryStore {
                 .with_context(|| "failed to run protoc successfully")?;
 
             let build_fd = FileSystem::join_paths(current_dir, ".plm/builds/build.pb");
-            let (fd, fd_bytes) = parse_fd_to_protobuf(build_fd)?;
+            let (fd, _) = parse_fd_to_protobuf(build_fd)?;
             let packages_to_files = parse_package_files_map(&fd);
 
             // Generate package metadata
@@ -276,18 +557,20 @@ impl LibraryStore {
                 }
             };
 
-            let pkgs = packages_to_files.into_iter().map(parse_packages);
-            let release_id = crate::utils::hash_fd_set(fd_bytes);
+            let pkgs: Vec<crate::Package> =
+                packages_to_files.into_iter().map(parse_packages).collect();
+            // The registry verifies the uploaded files against this checksum before publishing
+            let checksum = crate::utils::hash_files(pkgs.iter().flat_map(|pkg| pkg.files.iter()));
             let mut lib_md = HashMap::new();
 
-            lib_md.insert("checksum".to_string(), release_id.to_string());
+            lib_md.insert("checksum".to_string(), checksum);
             lib_md.insert("description".to_string(), manifest.description);
             let lib = crate::Library {
                 name: manifest.name,
                 version: manifest.version,
                 fd_set: fd_set_to_bytes(&fd),
                 metadata: lib_md,
-                packages: pkgs.collect(),
+                packages: pkgs,
                 dependencies: manifest.dependencies,
             };
 
@@ -370,3 +653,73 @@ fn parse_fd_to_protobuf(fd_set_path: PathBuf) -> Result<(FileDescriptorSet, Vec<
 
     Ok((fd, file))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Package;
+
+    fn release(version: &str, files: &[(&str, &str)]) -> Library {
+        Library {
+            name: "lib".to_string(),
+            version: version.to_string(),
+            packages: vec![Package {
+                files: files
+                    .iter()
+                    .map(|(name, content)| File {
+                        name: name.to_string(),
+                        content: content.as_bytes().to_vec(),
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_replaces_previous_release() {
+        let root = std::env::temp_dir().join(format!("plm-store-{}", std::process::id()));
+        let lib_dir = root.join("lib");
+
+        LibraryStore::write_to(
+            &root,
+            release("1.0.0", &[("a.proto", "a"), ("b.proto", "b")]),
+        )
+        .await
+        .unwrap();
+        assert!(lib_dir.join("b.proto").exists());
+
+        let updated = release("1.1.0", &[("a.proto", "a2")]);
+        LibraryStore::write_to(&root, updated.clone())
+            .await
+            .unwrap();
+        assert!(lib_dir.join("a.proto").exists());
+        assert!(!lib_dir.join("b.proto").exists());
+        assert_eq!(
+            LibraryStore::checksum_dir(&lib_dir).unwrap(),
+            hash_files(&updated.packages[0].files)
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_rejects_escaping_paths() {
+        let root = std::env::temp_dir().join(format!("plm-store-escape-{}", std::process::id()));
+
+        for name in ["../../x.proto", "/tmp/x.proto"] {
+            let library = release("1.0.0", &[("a.proto", "a"), (name, "x")]);
+            assert!(LibraryStore::write_to(&root, library).await.is_err());
+        }
+        let mut library = release("1.0.0", &[("a.proto", "a")]);
+        for name in ["../lib", "", "."] {
+            library.name = name.to_string();
+            assert!(LibraryStore::write_to(&root, library.clone())
+                .await
+                .is_err());
+        }
+
+        assert!(!root.exists());
+    }
+}
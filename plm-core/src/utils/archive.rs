@@ -0,0 +1,160 @@
+// Copyright 2023 PLM Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    io::Read,
+    path::{Component, Path},
+};
+
+use anyhow::{anyhow, Context, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+use crate::{
+    plm::{
+        library::v1::Library,
+        package::v1::{File, Package},
+    },
+    Manifest,
+};
+
+/// Name of the release manifest entry within a library tarball
+pub const MANIFEST_ENTRY: &str = "proto-package.json";
+
+/// Packs a library release into a gzipped tarball.
+///
+/// The tarball holds a `proto-package.json` manifest with the release name, version and
+/// dependencies, followed by every package file under its relative path.
+pub fn pack_library(library: &Library) -> Result<Vec<u8>> {
+    let manifest = Manifest {
+        name: library.name.clone(),
+        version: library.version.clone(),
+        description: library
+            .metadata
+            .get("description")
+            .cloned()
+            .unwrap_or_default(),
+        dependencies: library.dependencies.clone(),
+        ..Default::default()
+    };
+    let manifest = serde_json::to_vec_pretty(&manifest)
+        .with_context(|| format!("failed to serialize {} manifest", library.name))?;
+
+    let mut tarball = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    append_entry(&mut tarball, MANIFEST_ENTRY, &manifest)?;
+    for file in library.packages.iter().flat_map(|pkg| pkg.files.iter()) {
+        append_entry(&mut tarball, &file.name, &file.content)?;
+    }
+
+    let gz = tarball
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .with_context(|| format!("failed to compress {}", library.name))?;
+    Ok(gz)
+}
+
+/// Unpacks a gzipped tarball produced by [`pack_library`] back into a library release.
+pub fn unpack_library(gz: &[u8]) -> Result<Library> {
+    let mut tarball = tar::Archive::new(GzDecoder::new(gz));
+    let mut manifest = None;
+    let mut files = vec![];
+
+    for entry in tarball
+        .entries()
+        .with_context(|| "failed to read library tarball")?
+    {
+        let mut entry = entry.with_context(|| "failed to read library tarball entry")?;
+        let path = entry.path()?.to_path_buf();
+        if !is_relative_path(&path) {
+            return Err(anyhow!("invalid library tarball entry: {}", path.display()));
+        }
+
+        let mut content = Vec::with_capacity(entry.size() as usize);
+        entry
+            .read_to_end(&mut content)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+
+        let name = path.to_string_lossy().into_owned();
+        if name == MANIFEST_ENTRY {
+            let parsed: Manifest = serde_json::from_slice(&content)
+                .with_context(|| "failed to parse library tarball manifest")?;
+            manifest = Some(parsed);
+        } else {
+            files.push(File { name, content });
+        }
+    }
+
+    let manifest = manifest.ok_or_else(|| anyhow!("library tarball is missing its manifest"))?;
+    Ok(Library {
+        name: manifest.name,
+        version: manifest.version,
+        dependencies: manifest.dependencies,
+        packages: vec![Package {
+            files,
+            ..Default::default()
+        }],
+        ..Default::default()
+    })
+}
+
+fn append_entry<W: std::io::Write>(
+    tarball: &mut tar::Builder<W>,
+    name: &str,
+    content: &[u8],
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    tarball
+        .append_data(&mut header, name, content)
+        .with_context(|| format!("failed to add {} to tarball", name))
+}
+
+/// Whether a file path stays within the directory it is joined to, e.g: tarball entries are
+/// written under `proto_modules`, so they must never escape it
+pub fn is_relative_path(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack_library() {
+        let library = Library {
+            name: "shop".to_string(),
+            version: "1.2.0".to_string(),
+            dependencies: HashMap::from([("money".to_string(), "^0.3".to_string())]),
+            packages: vec![Package {
+                name: "orders.v1".to_string(),
+                files: vec![File {
+                    name: "orders/v1/orders.proto".to_string(),
+                    content: b"syntax = \"proto3\";\npackage orders.v1;\n".to_vec(),
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let unpacked = unpack_library(&pack_library(&library).unwrap()).unwrap();
+        assert_eq!(unpacked.name, library.name);
+        assert_eq!(unpacked.version, library.version);
+        assert_eq!(unpacked.dependencies, library.dependencies);
+        assert_eq!(unpacked.packages[0].files, library.packages[0].files);
+    }
+}
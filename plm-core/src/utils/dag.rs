@@ -71,3 +71,104 @@ impl<T: Eq + Hash + Clone> Dag<T> {
         false
     }
 }
+
+impl<T: Eq + Hash + Clone + Ord> Dag<T> {
+    /// Finds a dependency cycle, as the chain of packages leading back to its first package,
+    /// e.g. `[a, b, a]`. Packages are walked in order, so the same graph reports the same cycle.
+    pub fn find_cycle(&self) -> Option<Vec<T>> {
+        let mut names: Vec<&T> = self.nodes.keys().collect();
+        names.sort();
+
+        let mut visited = HashSet::new();
+        names
+            .into_iter()
+            .find_map(|name| self.visit(name, &mut vec![], &mut visited))
+    }
+
+    /// Depth-first walk from `name`, where `path` holds the packages leading to it and
+    /// `visited` the packages whose dependencies are known to be acyclic
+    fn visit<'a>(
+        &'a self,
+        name: &'a T,
+        path: &mut Vec<&'a T>,
+        visited: &mut HashSet<&'a T>,
+    ) -> Option<Vec<T>> {
+        if let Some(start) = path.iter().position(|n| *n == name) {
+            let mut cycle: Vec<T> = path[start..].iter().map(|n| (*n).clone()).collect();
+            cycle.push(name.clone());
+            return Some(cycle);
+        }
+        if visited.contains(name) {
+            return None;
+        }
+
+        path.push(name);
+        if let Some(package) = self.nodes.get(name) {
+            let mut dependencies: Vec<&T> = package.dependencies.iter().collect();
+            dependencies.sort();
+            for dep in dependencies {
+                if let Some(cycle) = self.visit(dep, path, visited) {
+                    return Some(cycle);
+                }
+            }
+        }
+        path.pop();
+        visited.insert(name);
+        None
+    }
+
+    /// Dependencies missing from the graph, as sorted `(dependent, dependency)` pairs
+    pub fn missing_dependencies(&self) -> Vec<(T, T)> {
+        let mut missing: Vec<(T, T)> = self
+            .nodes
+            .values()
+            .flat_map(|package| {
+                package
+                    .dependencies
+                    .iter()
+                    .filter(|dep| !self.nodes.contains_key(*dep))
+                    .map(|dep| (package.name.clone(), dep.clone()))
+            })
+            .collect();
+        missing.sort();
+        missing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dag(edges: &[(&'static str, &[&'static str])]) -> Dag<&'static str> {
+        Dag {
+            nodes: edges
+                .iter()
+                .map(|(name, deps)| {
+                    let package = Package {
+                        name: *name,
+                        dependencies: deps.iter().copied().collect(),
+                    };
+                    (*name, package)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let acyclic = dag(&[("a", &["b", "c"]), ("b", &["c"]), ("c", &[])]);
+        assert_eq!(acyclic.find_cycle(), None);
+
+        let cyclic = dag(&[("a", &["b"]), ("b", &["c"]), ("c", &["b"])]);
+        assert_eq!(cyclic.find_cycle(), Some(vec!["b", "c", "b"]));
+
+        let self_loop = dag(&[("a", &["a"])]);
+        assert_eq!(self_loop.find_cycle(), Some(vec!["a", "a"]));
+    }
+
+    #[test]
+    fn test_missing_dependencies() {
+        let graph = dag(&[("a", &["b", "d"]), ("b", &["e"])]);
+        assert_eq!(graph.missing_dependencies(), vec![("a", "d"), ("b", "e")]);
+    }
+}
@@ -137,8 +137,8 @@ impl FileSystem {
 
     pub fn write_yaml<T: Serialize>(file_path: &str, value: &T) -> io::Result<()> {
         trace!("writing .yaml file: {}", file_path);
-        let yaml_string = serde_yaml::to_string(value)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        let yaml_string =
+            serde_yaml::to_string(value).map_err(|e| io::Error::other(e.to_string()))?;
         let mut file = File::create(file_path)?;
         file.write_all(yaml_string.as_bytes())?;
         Ok(())
@@ -0,0 +1,224 @@
+// Copyright 2023 PLM Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeSet, HashSet};
+
+use crate::plm::package::v1::File;
+
+/// The top-level `package` and `import` statements of a `.proto` file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ProtoHeader {
+    pub package: Option<String>,
+    pub imports: Vec<String>,
+}
+
+/// Parses the `package` and `import` statements of a `.proto` file source, without compiling it.
+pub fn parse_header(content: &str) -> ProtoHeader {
+    let mut header = ProtoHeader::default();
+
+    for statement in strip_comments(content).split(';') {
+        let statement = statement.trim_start_matches(|c: char| c == '}' || c.is_whitespace());
+        if let Some(package) = statement.strip_prefix("package") {
+            if package.starts_with(char::is_whitespace) {
+                header.package = Some(package.trim().to_string());
+            }
+        } else if let Some(import) = statement.strip_prefix("import") {
+            let import = import.trim();
+            let import = import
+                .strip_prefix("public")
+                .or_else(|| import.strip_prefix("weak"))
+                .unwrap_or(import)
+                .trim();
+            if let Some(path) = import
+                .strip_prefix('"')
+                .and_then(|path| path.strip_suffix('"'))
+            {
+                header.imports.push(path.to_string());
+            }
+        }
+    }
+
+    header
+}
+
+/// Selects the files of the requested packages along with every file they import, transitively.
+///
+/// Packages may be given as `<package>` or `<library>/<package>`. Imports are matched against the
+/// library files either as-is or prefixed by the library name, the way they resolve once vendored
+/// under `proto_modules`; imports of other libraries are left to the dependencies.
+pub fn select_packages(
+    lib_name: &str,
+    files: Vec<File>,
+    packages: &[String],
+) -> Result<Vec<File>, String> {
+    let lib_prefix = format!("{}/", lib_name);
+    let headers: Vec<ProtoHeader> = files
+        .iter()
+        .map(|f| parse_header(&String::from_utf8_lossy(&f.content)))
+        .collect();
+
+    let mut pending = vec![];
+    for package in packages {
+        let package = package.strip_prefix(&lib_prefix).unwrap_or(package);
+        let matched: Vec<usize> = headers
+            .iter()
+            .enumerate()
+            .filter(|(_, header)| header.package.as_deref() == Some(package))
+            .map(|(index, _)| index)
+            .collect();
+        if matched.is_empty() {
+            let available: BTreeSet<&str> = headers
+                .iter()
+                .filter_map(|h| h.package.as_deref())
+                .collect();
+            return Err(format!(
+                "package {} not found in {}, available packages: {}",
+                package,
+                lib_name,
+                available.into_iter().collect::<Vec<_>>().join(", ")
+            ));
+        }
+        pending.extend(matched);
+    }
+
+    let mut selected = HashSet::new();
+    while let Some(index) = pending.pop() {
+        if !selected.insert(index) {
+            continue;
+        }
+        for import in &headers[index].imports {
+            let import = import.strip_prefix(&lib_prefix).unwrap_or(import);
+            if let Some(imported) = files.iter().position(|f| f.name == import) {
+                pending.push(imported);
+            }
+        }
+    }
+
+    Ok(files
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| selected.contains(index))
+        .map(|(_, file)| file)
+        .collect())
+}
+
+/// Blanks out the comments of a `.proto` source, leaving string literals, which may hold `//` or
+/// `/*` themselves, untouched
+fn strip_comments(content: &str) -> String {
+    let mut stripped = String::with_capacity(content.len());
+    let mut rest = content;
+    while !rest.is_empty() {
+        if let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') {
+            let mut escaped = false;
+            let end = rest[1..]
+                .find(|c: char| {
+                    let closing = !escaped && (c == quote || c == '\n');
+                    escaped = !escaped && c == '\\';
+                    closing
+                })
+                .map_or(rest.len(), |end| end + 2);
+            stripped.push_str(&rest[..end]);
+            rest = &rest[end..];
+        } else if let Some(comment) = rest.strip_prefix("//") {
+            rest = comment.find('\n').map_or("", |end| &comment[end..]);
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.find("*/").map_or("", |end| &comment[end + 2..]);
+            stripped.push(' ');
+        } else {
+            let c = rest.chars().next().unwrap();
+            stripped.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, content: &str) -> File {
+        File {
+            name: name.to_string(),
+            content: content.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let header = parse_header(
+            r#"// Copyright; PLM
+            syntax = "proto3";
+            /* package ignored.v1; */
+            package shop.orders.v1;
+            import "google/protobuf/timestamp.proto";
+            import public "shop/common/v1/money.proto";
+            option java_package = "com.shop";
+            message Order { string id = 1; }"#,
+        );
+        assert_eq!(header.package, Some("shop.orders.v1".to_string()));
+        assert_eq!(
+            header.imports,
+            vec![
+                "google/protobuf/timestamp.proto".to_string(),
+                "shop/common/v1/money.proto".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_header_strings() {
+        let header = parse_header(
+            r#"syntax = "proto3";
+            option go_package = "http://example.com/shop/v1;shopv1";
+            option (doc) = "see /* below \" //";
+            package shop.v1;
+            import "shop/common/v1/money.proto";"#,
+        );
+        assert_eq!(header.package, Some("shop.v1".to_string()));
+        assert_eq!(
+            header.imports,
+            vec!["shop/common/v1/money.proto".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_select_packages() {
+        let files = vec![
+            file(
+                "common/v1/money.proto",
+                "package common.v1; message Money {}",
+            ),
+            file(
+                "orders/v1/orders.proto",
+                r#"package orders.v1;
+                import "shop/common/v1/money.proto";
+                import "google/protobuf/timestamp.proto";"#,
+            ),
+            file("users/v1/users.proto", "package users.v1;"),
+        ];
+
+        let selected = select_packages("shop", files.clone(), &["shop/orders.v1".to_string()])
+            .unwrap()
+            .into_iter()
+            .map(|f| f.name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            selected,
+            vec!["common/v1/money.proto", "orders/v1/orders.proto"]
+        );
+
+        assert!(select_packages("shop", files, &["billing.v1".to_string()]).is_err());
+    }
+}
@@ -69,6 +69,23 @@ pub fn resolve_versions(
     Ok(resolved)
 }
 
+/// Checks whether a version satisfies a semver requirement, an empty requirement matches any version.
+pub fn satisfies(requirement: &str, version: &str) -> bool {
+    if requirement.is_empty() {
+        return true;
+    }
+
+    match (VersionReq::parse(requirement), Version::parse(version)) {
+        (Ok(req), Ok(version)) => req.matches(&version),
+        _ => false,
+    }
+}
+
+/// Checks whether a semver requirement parses, an empty requirement stands for any version.
+pub fn is_valid_requirement(requirement: &str) -> bool {
+    requirement.is_empty() || VersionReq::parse(requirement).is_ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -94,4 +111,16 @@ mod tests {
         assert_eq!(resolved.get("package_a").unwrap(), "1.0.0");
         assert_eq!(resolved.get("package_b").unwrap(), "1.0.0");
     }
+
+    #[test]
+    fn test_satisfies() {
+        assert!(satisfies("", "0.1.0"));
+        assert!(satisfies("^1.2", "1.9.0"));
+        assert!(satisfies("1.2.3", "1.4.0"));
+        assert!(!satisfies(">=0.9, <2", "2.0.0"));
+        assert!(!satisfies("^1", "not-a-version"));
+        assert!(is_valid_requirement(""));
+        assert!(is_valid_requirement("<0.3"));
+        assert!(!is_valid_requirement("latest"));
+    }
 }
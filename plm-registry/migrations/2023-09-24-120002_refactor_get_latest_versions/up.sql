@@ -1,5 +1,5 @@
 -- Your SQL goes here
-DROP FUNCTION get_latest_version
+DROP FUNCTION get_latest_version;
 CREATE OR REPLACE FUNCTION get_latest_version(lib_name text)
 RETURNS TABLE (max_version_id INT, max_version_number TEXT) AS $$
 DECLARE
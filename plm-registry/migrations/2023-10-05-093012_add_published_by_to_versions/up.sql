@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE versions
+ADD COLUMN published_by INT REFERENCES users(user_id);
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE library_owners (
+    lib_id INT NOT NULL REFERENCES libraries(lib_id) ON DELETE CASCADE,
+    user_id INT NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    PRIMARY KEY (lib_id, user_id)
+);
+
+-- The first publisher of an existing library becomes its owner
+INSERT INTO library_owners (lib_id, user_id)
+SELECT DISTINCT ON (library_id) library_id, published_by
+FROM versions
+WHERE published_by IS NOT NULL
+ORDER BY library_id, created_at, id;
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- Roles follow `plm.user.v1.Role`: 1 = ADMIN, 2 = CONTRIBUTOR
+ALTER TABLE user_organizations
+ADD COLUMN role INT NOT NULL DEFAULT 2;
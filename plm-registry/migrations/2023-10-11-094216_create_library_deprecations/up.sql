@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE library_deprecations (
+    id SERIAL PRIMARY KEY,
+    lib_id INT NOT NULL REFERENCES libraries(lib_id) ON DELETE CASCADE,
+    -- Semver range of the deprecated versions, empty when the whole library is deprecated
+    version_range VARCHAR(255) NOT NULL DEFAULT '',
+    message TEXT NOT NULL,
+    replacement VARCHAR(255),
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    UNIQUE(lib_id, version_range)
+);
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- Digest of the release files, unknown for releases published before it was recorded
+ALTER TABLE versions
+ADD COLUMN checksum VARCHAR(64);
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+-- Uploads staged in the registry storage until they are published, by uploader
+CREATE TABLE staged_uploads (
+    token VARCHAR(36) PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    library VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
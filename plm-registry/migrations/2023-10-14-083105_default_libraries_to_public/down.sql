@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE libraries ALTER COLUMN public DROP DEFAULT;
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+-- Libraries are readable by anyone unless published as private
+ALTER TABLE libraries ALTER COLUMN public SET DEFAULT TRUE;
+
+-- Libraries released before access tracking were stored as not public, while meant to be
+UPDATE libraries SET public = TRUE
+WHERE NOT EXISTS (
+    SELECT 1 FROM versions v
+    WHERE v.library_id = libraries.lib_id AND v.published_by IS NOT NULL
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+-- Nothing to undo, the previous access of these libraries is not known anymore
+SELECT 1;
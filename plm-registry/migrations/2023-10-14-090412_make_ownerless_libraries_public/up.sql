@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- Libraries released before owners were tracked have nobody to grant them access, so they
+-- can only be read when public
+UPDATE libraries SET public = TRUE
+WHERE NOT EXISTS (
+    SELECT 1 FROM library_owners o WHERE o.lib_id = libraries.lib_id
+);
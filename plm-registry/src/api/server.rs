@@ -12,35 +12,38 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{env, net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, sync::Arc};
 
 // use tower::{ServiceBuilder, layer::{util::Stack, LayerFn}};
-use plm_core::{registry_service_server, user_service_server};
-use tonic::transport::Server as GrpcServer;
+use plm_core::{organization_service_server, registry_service_server, user_service_server};
+use tonic::{service::Interceptor, transport::Server as GrpcServer};
 use tracing::{debug, warn};
 
 use crate::{
+    auth::JwtKeys,
     psql::QueryLayer,
-    service::{RegistryService, UserService},
+    service::{OrganizationService, RegistryService, UserService},
     RegistryStorage,
 };
 
-lazy_static::lazy_static! {
-    pub static ref SECRET: String = env::var("PLM_SECRET").unwrap_or_else(|_| "default_secret".to_string());
-}
-
 #[derive(Clone)]
 pub struct RegistryServerBuilder {
     addr: Option<SocketAddr>,
     storage: Arc<Box<dyn RegistryStorage + Send + Sync>>,
+    jwt: Arc<JwtKeys>,
+    signup_enabled: bool,
+    admins: Arc<Vec<String>>,
 }
 
 impl RegistryServerBuilder {
-    pub fn new(storage: Box<dyn RegistryStorage + Send + Sync>) -> Self {
+    pub fn new(storage: Box<dyn RegistryStorage + Send + Sync>, jwt: JwtKeys) -> Self {
         let addr = "127.0.0.1:7575".parse().unwrap();
         Self {
             addr: Some(addr),
             storage: Arc::new(storage),
+            jwt: Arc::new(jwt),
+            signup_enabled: true,
+            admins: Arc::new(vec![]),
         }
     }
 
@@ -49,19 +52,40 @@ impl RegistryServerBuilder {
         self
     }
 
+    /// Closes the registry to new accounts, rejecting `CreateUser` calls
+    pub fn disable_signup(&mut self, disabled: bool) -> &mut Self {
+        self.signup_enabled = !disabled;
+        self
+    }
+
+    /// Sets the usernames of the registry admins
+    pub fn with_admins(&mut self, admins: Vec<String>) -> &mut Self {
+        self.admins = Arc::new(admins);
+        self
+    }
+
     pub fn build(self) -> RegistryServer {
         let query_layer = QueryLayer::new();
         let user = UserService {
             data: query_layer.clone(),
+            jwt: self.jwt.clone(),
+            signup_enabled: self.signup_enabled,
+            admins: self.admins.clone(),
         };
         let registry = RegistryService {
             data: query_layer.clone(),
             storage: self.storage,
+            admins: self.admins,
+        };
+        let organization = OrganizationService {
+            data: query_layer.clone(),
         };
         RegistryServer {
             addr: self.addr.unwrap(),
             registry,
             user,
+            organization,
+            auth_guard: AuthGuard { jwt: self.jwt },
         }
     }
 }
@@ -71,6 +95,8 @@ pub struct RegistryServer {
     addr: SocketAddr,
     registry: RegistryService,
     user: UserService,
+    organization: OrganizationService,
+    auth_guard: AuthGuard,
 }
 
 impl RegistryServer {
@@ -88,17 +114,21 @@ impl RegistryServer {
         let svc = registry_service_server::RegistryServiceServer::new(self.registry.clone())
             .max_decoding_message_size(100 * 1024 * 1024)
             .max_encoding_message_size(100 * 1024 * 1024);
-
-        // let intercepted_service = registry_service_server::RegistryServiceServer::with_interceptor(
-        //     self.registry.clone(),
-        //     auth_guard,
-        // );
+        let svc =
+            tonic::service::interceptor::InterceptedService::new(svc, self.auth_guard.clone());
 
         let server = server_builder
             .add_service(svc)
-            .add_service(user_service_server::UserServiceServer::new(
+            .add_service(user_service_server::UserServiceServer::with_interceptor(
                 self.user.clone(),
+                self.auth_guard.clone(),
             ))
+            .add_service(
+                organization_service_server::OrganizationServiceServer::with_interceptor(
+                    self.organization.clone(),
+                    self.auth_guard.clone(),
+                ),
+            )
             .serve(self.addr)
             .await;
 
@@ -118,35 +148,57 @@ impl RegistryServer {
 }
 use crate::utils::auth;
 
-/// This function will get called on each inbound request, if a `Status`
-/// is returned, it will cancel the request and return that status to the
-/// client.
-fn _auth_guard(req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
-    warn!("Intercepting request: {:?}", req);
+/// Authenticates each inbound request, if a `Status` is returned, it will cancel the request
+/// and return that status to the client.
+///
+/// A valid bearer token attaches its [`auth::AuthUser`] to the request extensions, a missing or
+/// empty one lets the request through anonymously and it's up to the handler to require a user.
+/// API tokens are attached as an [`auth::ApiTokenCredential`] instead.
+#[derive(Clone)]
+struct AuthGuard {
+    jwt: Arc<JwtKeys>,
+}
+
+impl Interceptor for AuthGuard {
+    fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        let token = match req.metadata().get("authorization") {
+            None => return Ok(req),
+            Some(t) => t
+                .to_str()
+                .map_err(|_| {
+                    tonic::Status::unauthenticated("Invalid token format should be: Bearer <token>")
+                })?
+                .to_string(),
+        };
 
-    match req.metadata().get("authorization") {
-        Some(t) => match _extract_bearer_token(t.to_str().unwrap()) {
+        match extract_bearer_token(&token) {
             None => Err(tonic::Status::unauthenticated(
                 "Invalid token format should be: Bearer <token>".to_string(),
             )),
-            Some(t) => {
-                if auth::validate_jwt_token(t, SECRET.as_bytes()).is_ok() {
+            Some("") => Ok(req),
+            // API tokens are looked up by the handlers, as interceptors can't reach the database
+            Some(t) if t.starts_with(auth::API_TOKEN_PREFIX) => {
+                req.extensions_mut().insert(auth::ApiTokenCredential {
+                    token_hash: plm_core::utils::hash_content(t.as_bytes()),
+                });
+                Ok(req)
+            }
+            Some(t) => match auth::validate_jwt_token(t, &self.jwt) {
+                Ok(user) => {
+                    req.extensions_mut().insert(user);
                     Ok(req)
-                } else {
+                }
+                Err(err) => {
+                    warn!("rejected auth token: {}", err);
                     Err(tonic::Status::unauthenticated("No valid auth token"))
                 }
-            }
-        },
-        _ => Err(tonic::Status::unauthenticated(
-            "Missing authorization metadata",
-        )),
+            },
+        }
     }
 }
 
-fn _extract_bearer_token(s: &str) -> Option<&str> {
-    if s.starts_with("Bearer ") && s.len() > "Bearer ".len() {
-        Some(&s["Bearer ".len()..])
-    } else {
-        None
-    }
+fn extract_bearer_token(s: &str) -> Option<&str> {
+    s.strip_prefix("Bearer")
+        .filter(|t| t.is_empty() || t.starts_with(' '))
+        .map(str::trim)
 }
@@ -12,30 +12,517 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use diesel::result::{DatabaseErrorKind, Error};
+use diesel::{
+    dsl::IntervalDsl,
+    result::{DatabaseErrorKind, Error},
+    OptionalExtension,
+};
 use plm_core::{
-    plm::registry::v1::{MetadataRequest, MetadataResponse, UploadRequest, Version},
+    organization_service_server,
+    plm::registry::v1::{
+        Access, Compressions, DeprecateRequest, Deprecation, ListOwnersRequest, ListOwnersResponse,
+        MetadataRequest, MetadataResponse, OwnerRequest, UploadRequest, UploadResponse, Version,
+        YankRequest,
+    },
     registry_service_server, user_service_server,
-    utils::auth,
-    CreateUserRequest, DownloadRequest, DownloadResponse, FullOrPartial, Library, LoginRequest,
-    LoginResponse, PublishRequest, User,
+    utils::{archive, auth, hash_content, hash_files, protos, versioning},
+    AddUserRequest, ApiToken, CreateOrganizationRequest, CreateTokenRequest, CreateTokenResponse,
+    CreateUserRequest, DownloadRequest, DownloadResponse, FullOrPartial, GetOrganizationRequest,
+    GetOrganizationResponse, Library, ListTokensResponse, LoginRequest, LoginResponse,
+    Organization, PublishRequest, RemoveOrganizationRequest, RemoveUserRequest, RevokeTokenRequest,
+    Role, UpdateUserRoleRequest, User,
 };
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, path::Path, sync::Arc};
 use tokio_stream::StreamExt;
 use tonic::{async_trait, Request, Response, Status};
 use tracing::{debug, error, info, warn};
 
 use crate::{
-    api::server::SECRET,
-    models::{NewDependency, NewVersion},
+    auth::{self as registry_auth, ApiTokenCredential, AuthUser, JwtKeys, Scope},
+    error::RegistryError,
+    models::{
+        self, NewApiToken, NewDependency, NewLibraryDeprecation, NewLibraryOwner, NewOrganization,
+        NewStagedUpload, NewUserOrganization, NewVersion,
+    },
     psql::QueryLayer,
+    types::RegistryResult,
     RegistryStorage,
 };
 
+/// Staged uploads which are not published within this many hours are discarded
+const STAGED_UPLOAD_TTL_HOURS: i32 = 24;
+
 #[derive(Clone)]
 pub struct RegistryService {
     pub(crate) data: QueryLayer,
     pub(crate) storage: Arc<Box<dyn RegistryStorage + Sync + Send>>,
+    pub(crate) admins: Arc<Vec<String>>,
+}
+
+impl RegistryService {
+    /// Runs a storage operation on the blocking thread pool, as storages do blocking I/O
+    async fn with_storage<T, F>(&self, op: F) -> RegistryResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn RegistryStorage) -> RegistryResult<T> + Send + 'static,
+    {
+        let storage = self.storage.clone();
+        tokio::task::spawn_blocking(move || op(storage.as_ref().as_ref()))
+            .await
+            .map_err(|e| RegistryError::StorageError(format!("storage task failed: {}", e)))?
+    }
+
+    /// Stages every file of an upload stream, which must all belong to the same release, and
+    /// returns the name of its library
+    async fn stage_upload(
+        &self,
+        token: &str,
+        caller: &Caller,
+        stream: &mut tonic::Streaming<UploadRequest>,
+    ) -> Result<String, Status> {
+        let mut library: Option<String> = None;
+        while let Some(upload) = stream.next().await {
+            let upload = upload?;
+            let file = upload
+                .file
+                .as_ref()
+                .ok_or_else(|| Status::invalid_argument("upload is missing its file"))?;
+            if !archive::is_relative_path(Path::new(&file.name)) {
+                return Err(Status::invalid_argument(format!(
+                    "invalid file name: {}",
+                    file.name
+                )));
+            }
+            match &library {
+                Some(lib) if lib != &upload.library => {
+                    return Err(Status::invalid_argument(format!(
+                        "an upload must hold a single release, got: {} and {}",
+                        lib, upload.library
+                    )));
+                }
+                Some(_) => {}
+                None => {
+                    let (lib_name, _) = parse_release_ref(&upload.library);
+                    caller.require(Scope::Publish(lib_name.to_string()))?;
+                    library = Some(upload.library.clone());
+                }
+            }
+            debug!("  ==> Upload = {}/{}", upload.library, file.name);
+
+            let (token, file) = (token.to_string(), file.clone());
+            self.with_storage(move |storage| storage.stage(&token, &file))
+                .await
+                .map_err(|e| Status::internal(format!("Failed to write file: {:?}", e)))?;
+        }
+        match library {
+            Some(lib) => Ok(parse_release_ref(&lib).0.to_string()),
+            None => Err(Status::invalid_argument("upload has no files")),
+        }
+    }
+
+    /// Checks an upload token was staged by `user` for `lib_name`, as only its uploader may
+    /// publish it
+    async fn check_staged_upload(
+        &self,
+        token: &str,
+        user: AuthUser,
+        lib_name: &str,
+    ) -> Result<(), Status> {
+        let staged = self
+            .data
+            .get_staged_upload(token)
+            .await
+            .map_err(|e| Status::internal(format!("{:?}", e)))?
+            .ok_or_else(|| Status::not_found(format!("unknown upload: {}", token)))?;
+        if staged.user_id != user.user_id || staged.library != lib_name {
+            return Err(Status::permission_denied(format!(
+                "upload {} was not staged by you for {}",
+                token, lib_name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Verifies the files staged under an upload token against the release `checksum` metadata
+    async fn verify_upload(&self, token: &str, lib: &Library) -> Result<(), Status> {
+        let token = token.to_string();
+        let staged = self
+            .with_storage(move |storage| storage.load_staged(&token))
+            .await
+            .map_err(|e| Status::not_found(format!("{:?}", e)))?;
+        let checksum = hash_files(&staged);
+        match lib.metadata.get("checksum") {
+            Some(expected) if expected == &checksum => Ok(()),
+            Some(expected) => Err(Status::failed_precondition(format!(
+                "uploaded files checksum {} does not match the release checksum {}",
+                checksum, expected
+            ))),
+            None => Err(Status::invalid_argument(
+                "release is missing its checksum metadata",
+            )),
+        }
+    }
+
+    /// Removes a release whose files failed to be stored, restoring the library as it was
+    /// before, only logging failures as it's always a cleanup
+    async fn revert_release(
+        &self,
+        lib: &models::Library,
+        version_id: i32,
+        previous_public: Option<bool>,
+    ) {
+        let mut conn = self.data.conn.lock().await;
+        let reverted = conn.build_transaction().run(|c| {
+            self.data.remove_version(version_id, c)?;
+            match previous_public {
+                None => {
+                    self.data.remove_library(lib.lib_id, c)?;
+                }
+                Some(public) if public != lib.public => {
+                    self.data.set_library_access(lib.lib_id, public, c)?;
+                }
+                Some(_) => {}
+            }
+            diesel::QueryResult::Ok(())
+        });
+        if let Err(e) = reverted {
+            error!("failed to revert {}: {:?}", lib.name, e);
+        }
+    }
+
+    /// Drops a staged upload, only logging failures as it's always a cleanup
+    async fn discard_upload(&self, token: &str) {
+        let owned_token = token.to_string();
+        if let Err(e) = self
+            .with_storage(move |storage| storage.discard(&owned_token))
+            .await
+        {
+            error!("failed to discard upload {}: {:?}", token, e);
+        }
+        if let Err(e) = self.data.remove_staged_upload(token).await {
+            error!("failed to remove upload {}: {:?}", token, e);
+        }
+    }
+
+    /// Drops the uploads which were staged but never published in time
+    async fn discard_expired_uploads(&self) {
+        let expired = match self
+            .data
+            .get_expired_staged_uploads(STAGED_UPLOAD_TTL_HOURS.hours())
+            .await
+        {
+            Ok(expired) => expired,
+            Err(e) => {
+                error!("failed to list expired uploads: {:?}", e);
+                return;
+            }
+        };
+        for upload in expired {
+            debug!("discarding expired upload: {}", upload.token);
+            self.discard_upload(&upload.token).await;
+        }
+    }
+
+    /// Loads a release with its files, either the pinned `lib_version` or the latest one
+    async fn load_release(
+        &self,
+        lib_name: &str,
+        lib_version: Option<&str>,
+        user: Option<AuthUser>,
+    ) -> Result<Library, Status> {
+        let version_id = match lib_version {
+            Some(v) => {
+                self.data
+                    .get_version_for_lib(lib_name, v)
+                    .await
+                    .map_err(|e| {
+                        Status::internal(format!(
+                            "failed to fetch library: {}:{} - {:?}",
+                            lib_name, v, e
+                        ))
+                    })?
+                    .ok_or_else(|| {
+                        Status::not_found(format!("library release not found: {}:{}", lib_name, v))
+                    })?
+                    .id
+            }
+            None => match self.data.get_latest_version_for_lib(lib_name).await {
+                Ok(Some(latest)) => latest.max_version_id,
+                // `get_latest_version` yields a row of nulls for unknown libraries
+                Ok(None) | Err(Error::NotFound) | Err(Error::DeserializationError(_)) => {
+                    return Err(Status::not_found(format!(
+                        "library release not found: {}",
+                        lib_name
+                    )))
+                }
+                Err(e) => {
+                    return Err(Status::internal(format!(
+                        "failed to fetch library: {} - {:?}",
+                        lib_name, e
+                    )))
+                }
+            },
+        };
+
+        let (release, mut versions) = self
+            .data
+            .get_async_release(lib_name, Some(version_id), None)
+            .await
+            .map_err(|e| Status::internal(format!("error on fetching library: {:?}", e)))?
+            .ok_or_else(|| Status::not_found(format!("library release not found: {}", lib_name)))?;
+        if !self.can_read(&release, user).await? {
+            return Err(Status::not_found(match lib_version {
+                Some(v) => format!("library release not found: {}:{}", lib_name, v),
+                None => format!("library release not found: {}", lib_name),
+            }));
+        }
+        let release_version = versions
+            .pop()
+            .ok_or_else(|| Status::not_found(format!("library release not found: {}", lib_name)))?;
+
+        let mut lib = Library {
+            name: release.name,
+            version: release_version.version_number,
+            dependencies: self.get_release_dependencies(release_version.id).await?,
+            ..Default::default()
+        };
+        let lib_full_path = format!("{}/{}", lib.name, lib.version);
+        debug!("loading release files from: {}", lib_full_path);
+        let path = lib_full_path.clone();
+        let files = self
+            .with_storage(move |storage| storage.load(&path))
+            .await
+            .map_err(|e| Status::internal(format!("failed to fetch proto files: {}", e)))?;
+        if let Some(checksum) = &release_version.checksum {
+            if &hash_files(&files) != checksum {
+                error!(
+                    "stored files of {} don't match their checksum",
+                    lib_full_path
+                );
+                return Err(Status::data_loss(format!(
+                    "stored files of {}:{} are corrupted",
+                    lib.name, lib.version
+                )));
+            }
+        }
+        lib.packages.push(plm_core::Package {
+            files,
+            ..Default::default()
+        });
+        Ok(lib)
+    }
+
+    /// Fetches a library by name, failing with `NotFound` for unknown ones
+    async fn find_library(&self, lib_name: &str) -> Result<models::Library, Status> {
+        self.data
+            .get_library(lib_name.to_string())
+            .await
+            .map_err(|e| {
+                Status::internal(format!("failed to fetch library {}: {:?}", lib_name, e))
+            })?
+            .ok_or_else(|| Status::not_found(format!("library not found: {}", lib_name)))
+    }
+
+    /// Whether `user` may read a library, private ones are limited to their owners and the
+    /// members of their organization
+    async fn can_read(
+        &self,
+        lib: &models::Library,
+        user: Option<AuthUser>,
+    ) -> Result<bool, Status> {
+        if lib.public {
+            return Ok(true);
+        }
+        let Some(user) = user else {
+            return Ok(false);
+        };
+
+        if let Some(org_id) = lib.org_id {
+            let membership = self
+                .data
+                .get_membership(org_id, user.user_id)
+                .await
+                .map_err(|e| {
+                    Status::internal(format!("failed to fetch {} membership: {:?}", lib.name, e))
+                })?;
+            if membership.is_some() {
+                return Ok(true);
+            }
+        }
+        let owners = self
+            .data
+            .get_library_owners(lib.lib_id)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("failed to fetch {} owners: {:?}", lib.name, e))
+            })?;
+        Ok(owners.iter().any(|o| o.user_id == user.user_id))
+    }
+
+    /// Checks `user` is allowed to manage the releases of `lib`, which are open to its owners
+    /// and, for scoped libraries, to the contributors of their organization
+    async fn require_maintainer(
+        &self,
+        lib: &models::Library,
+        user: AuthUser,
+    ) -> Result<(), Status> {
+        if let Some(org_id) = lib.org_id {
+            let membership = self
+                .data
+                .get_membership(org_id, user.user_id)
+                .await
+                .map_err(|e| {
+                    Status::internal(format!("failed to fetch {} membership: {:?}", lib.name, e))
+                })?;
+            if membership.is_some_and(|m| role_allows(m.role, Role::Contributor)) {
+                return Ok(());
+            }
+        }
+        let owners = self
+            .data
+            .get_library_owners(lib.lib_id)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("failed to fetch {} owners: {:?}", lib.name, e))
+            })?;
+        if owners.iter().any(|o| o.user_id == user.user_id) {
+            Ok(())
+        } else if self.can_read(lib, Some(user)).await? {
+            Err(Status::permission_denied(format!(
+                "only the owners of {} are allowed to manage its releases",
+                lib.name
+            )))
+        } else {
+            Err(Status::not_found(format!(
+                "library not found: {}",
+                lib.name
+            )))
+        }
+    }
+
+    /// Fetches the owners of a library, which must include the requesting `user`
+    async fn require_owner(
+        &self,
+        lib: &models::Library,
+        user: AuthUser,
+    ) -> Result<Vec<models::User>, Status> {
+        let owners = self
+            .data
+            .get_library_owners(lib.lib_id)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("failed to fetch {} owners: {:?}", lib.name, e))
+            })?;
+        if owners.iter().any(|o| o.user_id == user.user_id) {
+            Ok(owners)
+        } else {
+            Err(Status::permission_denied(format!(
+                "only the owners of {} are allowed to manage it",
+                lib.name
+            )))
+        }
+    }
+
+    /// Checks `user` is allowed to publish a release of `lib`, scoped `@org/name` libraries are
+    /// open to the contributors of their organization. Owners of other libraries are checked
+    /// within the publish transaction
+    async fn authorize_publish(
+        &self,
+        lib: &Library,
+        user: AuthUser,
+    ) -> Result<PublishTarget, Status> {
+        let release = self
+            .data
+            .get_async_release(&lib.name, None, None)
+            .await
+            .map_err(|e| Status::internal(format!("error on fetching library: {:?}", e)))?;
+
+        let org_id = match parse_scope(&lib.name)? {
+            None => None,
+            Some(scope) => {
+                let org = self
+                    .data
+                    .get_organization_by_name(scope)
+                    .await
+                    .map_err(|e| {
+                        Status::internal(format!("failed to fetch organization {}: {:?}", scope, e))
+                    })?
+                    .ok_or_else(|| {
+                        Status::not_found(format!("organization not found: {}", scope))
+                    })?;
+                let membership = self
+                    .data
+                    .get_membership(org.org_id, user.user_id)
+                    .await
+                    .map_err(|e| {
+                        Status::internal(format!("failed to fetch {} membership: {:?}", scope, e))
+                    })?;
+                match membership {
+                    Some(m) if role_allows(m.role, Role::Contributor) => Some(org.org_id),
+                    _ => {
+                        return Err(Status::permission_denied(format!(
+                            "must be a contributor of {} to publish {}",
+                            scope, lib.name
+                        )))
+                    }
+                }
+            }
+        };
+
+        Ok(PublishTarget { release, org_id })
+    }
+
+    async fn find_user(&self, username: &str) -> Result<models::User, Status> {
+        self.data
+            .get_user(username)
+            .await
+            .map_err(|e| Status::internal(format!("failed to fetch user {}: {:?}", username, e)))?
+            .ok_or_else(|| Status::not_found(format!("user not found: {}", username)))
+    }
+
+    /// Maps a release dependencies to their declared version ranges
+    async fn get_release_dependencies(
+        &self,
+        version_id: i32,
+    ) -> Result<HashMap<String, String>, Status> {
+        let release_deps = self
+            .data
+            .get_async_dependencies_by_version(version_id)
+            .await
+            .map_err(|e| {
+                Status::internal(format!(
+                    "failed to fetch release {} dependencies: {:?}",
+                    version_id, e
+                ))
+            })?;
+
+        let mut dependencies = HashMap::with_capacity(release_deps.len());
+        for dep in release_deps {
+            match self
+                .data
+                .get_library_by_version(dep.dependent_version_id)
+                .await
+            {
+                Ok(Some((dep_lib, _))) => {
+                    dependencies.insert(dep_lib.name, dep.dependency_range);
+                }
+                Ok(None) => {
+                    error!(
+                        "release {} dependency {} points to a missing version",
+                        version_id, dep.id
+                    );
+                }
+                Err(e) => {
+                    return Err(Status::internal(format!(
+                        "failed to fetch release {} dependency {}: {:?}",
+                        version_id, dep.id, e
+                    )))
+                }
+            }
+        }
+
+        Ok(dependencies)
+    }
 }
 
 #[async_trait]
@@ -43,43 +530,42 @@ impl registry_service_server::RegistryService for RegistryService {
     async fn upload(
         &self,
         request: Request<tonic::Streaming<UploadRequest>>,
-    ) -> Result<Response<()>, Status> {
-        println!("Upload");
-
+    ) -> Result<Response<UploadResponse>, Status> {
+        let caller = require_caller(&self.data, request.extensions(), "upload").await?;
+        self.discard_expired_uploads().await;
         let mut stream = request.into_inner();
+        let upload_token = uuid::Uuid::new_v4().to_string();
+        debug!("staging upload: {}", upload_token);
 
-        while let Some(upload) = stream.next().await {
-            let upload = upload?;
-            let bind = upload.clone();
-            debug!(
-                "  ==> Upload = {}/{}",
-                bind.library,
-                bind.file.unwrap().name
-            );
-
-            // TODO: Save files
-            self.storage
-                .write(&upload)
-                .map_err(|e| tonic::Status::internal(format!("Failed to write file: {:?}", e)))?;
+        let library = match self.stage_upload(&upload_token, &caller, &mut stream).await {
+            Ok(library) => library,
+            Err(e) => {
+                self.discard_upload(&upload_token).await;
+                return Err(e);
+            }
+        };
+        let staged = NewStagedUpload {
+            token: &upload_token,
+            user_id: caller.user.user_id,
+            library: &library,
+        };
+        if let Err(e) = self.data.create_staged_upload(&staged).await {
+            self.discard_upload(&upload_token).await;
+            return Err(Status::internal(format!("{:?}", e)));
         }
 
-        Ok(Response::new(()))
+        Ok(Response::new(UploadResponse { upload_token }))
     }
 
     async fn metadata(
         &self,
         request: Request<MetadataRequest>,
     ) -> Result<Response<MetadataResponse>, tonic::Status> {
+        let user = reader(&self.data, request.extensions()).await?;
         let md_req = request.into_inner();
         info!("metadata lib: {:?}", md_req.clone());
         let lib = match self.data.get_library(md_req.library.clone()).await {
-            Ok(Some(lib)) => lib,
-            Ok(None) => {
-                return Err(tonic::Status::not_found(format!(
-                    "Library: {} doesn't exist, bug the author - or grab the package name.",
-                    md_req.library
-                )))
-            }
+            Ok(lib) => lib,
             Err(e) => {
                 return Err(tonic::Status::internal(format!(
                     "Failed to fetch metadata for library \"{}\": {:?}",
@@ -87,6 +573,16 @@ impl registry_service_server::RegistryService for RegistryService {
                 )))
             }
         };
+        // Private libraries are reported missing to whoever can't read them
+        let lib = match lib {
+            Some(lib) if self.can_read(&lib, user).await? => lib,
+            _ => {
+                return Err(tonic::Status::not_found(format!(
+                    "Library: {} doesn't exist, bug the author - or grab the package name.",
+                    md_req.library
+                )))
+            }
+        };
 
         let versions = self
             .data
@@ -130,15 +626,37 @@ impl registry_service_server::RegistryService for RegistryService {
                 name: lib.name.clone(),
                 version: ver.version_number.clone(),
                 dependencies,
+                yanked: ver.yanked,
+                yank_reason: ver.yank_reason.unwrap_or_default(),
+                checksum: ver.checksum.unwrap_or_default(),
             };
 
             hashed_versions.insert(ver.version_number.clone(), version);
         }
 
+        let deprecations = self
+            .data
+            .get_library_deprecations(lib.lib_id)
+            .await
+            .map_err(|e| {
+                tonic::Status::internal(format!(
+                    "Failed to fetch library \"{}\" deprecations: {:?}",
+                    md_req.library, e
+                ))
+            })?
+            .into_iter()
+            .map(|d| Deprecation {
+                version_range: d.version_range,
+                message: d.message,
+                replacement: d.replacement.unwrap_or_default(),
+            })
+            .collect();
+
         let md_res = MetadataResponse {
             name: lib.name,
             description: lib.description.unwrap_or("".to_string()),
             versions: hashed_versions,
+            deprecations,
         };
 
         Ok(Response::new(md_res))
@@ -148,104 +666,111 @@ impl registry_service_server::RegistryService for RegistryService {
         &self,
         request: Request<DownloadRequest>,
     ) -> Result<Response<DownloadResponse>, tonic::Status> {
+        let user = reader(&self.data, request.extensions()).await?;
         let lib_req = request.into_inner();
         info!("download lib: {:?}", lib_req.clone());
+        let compression = lib_req.compression();
 
-        match lib_req.full_or_partial {
+        let lib = match lib_req.full_or_partial {
             None => {
                 return Err(tonic::Status::invalid_argument(
                     "must specify a download request full/partial".to_string(),
                 ));
             }
-            Some(r) => {
-                match r {
-                    FullOrPartial::Full(full) => {
-                        let latest =
-                            self.data
-                                .get_latest_version_for_lib(&full)
-                                .await
-                                .map_err(|e| {
-                                    tonic::Status::not_found(format!(
-                                        "failed to fetch library: {} - {:?}",
-                                        full, e
-                                    ))
-                                })?;
-                        match latest {
-                            Some(latest) => {
-                                let release = self
-                                    .data
-                                    .get_async_release(&full, Some(latest.max_version_id), None)
-                                    .await
-                                    .map_err(|e| {
-                                        tonic::Status::internal(format!(
-                                            "error on fetching library: {:?}",
-                                            e
-                                        ))
-                                    })?;
-                                // self.data.get_release(, lib_version, lib_scope)
-                                let mut lib = Library::default();
-                                match release {
-                                    None => Err(tonic::Status::not_found(format!(
-                                        "library release not found: {}",
-                                        &full
-                                    ))),
-                                    Some(mut version) => {
-                                        lib.name = version.0.name;
-                                        // lib.dependencies = HashMap::with_capacity(capacity)
-                                        lib.version =
-                                            version.1.pop().unwrap().version_number.clone();
-                                        let mut lib_full_path = String::new();
-                                        lib_full_path
-                                            .push_str(&format!("{}/{}", lib.name, lib.version));
-                                        println!("{:?}", lib_full_path);
-                                        // TODO: Handle library file parsing
-                                        let files =
-                                            self.storage.load(&lib_full_path).map_err(|e| {
-                                                tonic::Status::internal(format!(
-                                                    "failed to fetch proto files: {}",
-                                                    e
-                                                ))
-                                            })?;
-                                        let mut downloaded_lib = DownloadResponse::default();
-                                        lib.packages.push(plm_core::Package {
-                                            files,
-                                            ..Default::default()
-                                        });
-                                        downloaded_lib.protobuf_or_gz =
-                                            Some(plm_core::ProtobufOrGz::Protobuf(lib));
-                                        Ok(Response::new(downloaded_lib))
-                                    }
-                                }
-                            }
-                            None => Err(tonic::Status::unimplemented(format!(
-                                "not implemented yet to install a pinned version: {}",
-                                &full
-                            ))),
-                        }
-                    }
-                    FullOrPartial::Partial(_partial) => {
-                        return Err(tonic::Status::unimplemented(
-                            "not implemented yet".to_string(),
-                        ));
-                    }
+            Some(FullOrPartial::Full(full)) => {
+                let (lib_name, lib_version) = parse_release_ref(&full);
+                self.load_release(lib_name, lib_version, user).await?
+            }
+            Some(FullOrPartial::Partial(partial)) => {
+                let (lib_name, lib_version) = parse_release_ref(&partial.name);
+                let mut lib = self.load_release(lib_name, lib_version, user).await?;
+                for pkg in lib.packages.iter_mut() {
+                    pkg.files = protos::select_packages(
+                        &lib.name,
+                        std::mem::take(&mut pkg.files),
+                        &partial.packages,
+                    )
+                    .map_err(Status::not_found)?;
                 }
+                lib
             }
-        }
+        };
+
+        // Partial downloads are checked against a digest of the selected files only
+        let checksum = hash_files(lib.packages.iter().flat_map(|pkg| &pkg.files));
+        let protobuf_or_gz = match compression {
+            Compressions::Protobuf => plm_core::ProtobufOrGz::Protobuf(lib),
+            Compressions::Gz => {
+                let gz = archive::pack_library(&lib).map_err(|e| {
+                    Status::internal(format!("failed to compress {}: {}", lib.name, e))
+                })?;
+                plm_core::ProtobufOrGz::Gz(gz)
+            }
+        };
+        Ok(Response::new(DownloadResponse {
+            protobuf_or_gz: Some(protobuf_or_gz),
+            checksum,
+        }))
     }
 
     async fn publish(
         &self,
         request: Request<PublishRequest>,
     ) -> Result<Response<()>, tonic::Status> {
-        let pub_req = request.into_inner().lib.unwrap();
-        info!("publish lib: {:?} : {}", pub_req.name, pub_req.version);
+        let caller = require_caller(&self.data, request.extensions(), "publish").await?;
+        let pub_req = request.into_inner();
+        let access = pub_req.access();
+        let upload_token = pub_req.upload_token;
+        if upload_token.is_empty() {
+            return Err(Status::invalid_argument(
+                "missing upload token, the release files must be uploaded before publishing",
+            ));
+        }
+        // Only registry issued tokens are accepted, as they are used as storage paths
+        let upload_token = match uuid::Uuid::parse_str(&upload_token) {
+            Ok(token) => token.to_string(),
+            Err(_) => {
+                return Err(Status::invalid_argument(format!(
+                    "invalid upload token: {}",
+                    upload_token
+                )))
+            }
+        };
+        let pub_req = pub_req
+            .lib
+            .ok_or_else(|| Status::invalid_argument("must specify a library to publish"))?;
+        info!(
+            "publish lib: {:?} : {} by user {}",
+            pub_req.name, pub_req.version, caller.user.user_id
+        );
 
-        let release = self
-            .data
-            .get_async_release(&pub_req.name, None, None)
-            .await
-            .map_err(|e| tonic::Status::internal(format!("error on fetching library: {:?}", e)))?;
+        // Expired uploads are gone before the check, so they can't be published anymore
+        self.discard_expired_uploads().await;
+        // Uploads of someone else are left untouched
+        self.check_staged_upload(&upload_token, caller.user, &pub_req.name)
+            .await?;
+        let user = match caller.require(Scope::Publish(pub_req.name.clone())) {
+            Ok(user) => user,
+            Err(e) => {
+                self.discard_upload(&upload_token).await;
+                return Err(e);
+            }
+        };
+        if let Err(e) = self.verify_upload(&upload_token, &pub_req).await {
+            self.discard_upload(&upload_token).await;
+            return Err(e);
+        }
+
+        let PublishTarget { release, org_id } = match self.authorize_publish(&pub_req, user).await {
+            Ok(target) => target,
+            Err(e) => {
+                self.discard_upload(&upload_token).await;
+                return Err(e);
+            }
+        };
         info!("{:?}", release);
+        // Access of the library before this release, none when the release creates it
+        let previous_public = release.as_ref().map(|r| r.0.public);
         let mut conn = self.data.conn.lock().await;
 
         // Starting Transaction for the whole publish phases, so any failure should roolback the release record
@@ -254,31 +779,64 @@ impl registry_service_server::RegistryService for RegistryService {
             let mut library = None;
             match release {
                 Some(r) => {
-                    library = Some(r.0);
+                    // Checked with the owners locked, so they can't be changed until the release
+                    // is recorded
+                    if org_id.is_none() {
+                        let owners = self.data.lock_library_owners(r.0.lib_id, c)?;
+                        if owners.is_empty() {
+                            return Err(PublishError::Denied(format!(
+                                "{} has no owners, ask a registry admin to add one before publishing it",
+                                pub_req.name
+                            )));
+                        }
+                        if !owners.contains(&user.user_id) {
+                            return Err(PublishError::Denied(format!(
+                                "only the owners of {} are allowed to publish it",
+                                pub_req.name
+                            )));
+                        }
+                    }
+                    let public = match access {
+                        Access::Public => true,
+                        Access::Private => false,
+                        Access::UnknownAccess => r.0.public,
+                    };
+                    if public != r.0.public {
+                        library = Some(self.data.set_library_access(r.0.lib_id, public, c)?);
+                    } else {
+                        library = Some(r.0);
+                    }
                 }
                 None => {
-                    let release = self.data.create_release(&pub_req, c)?;
+                    // Libraries of an organization are kept private unless published otherwise
+                    let public = match access {
+                        Access::Public => true,
+                        Access::Private => false,
+                        Access::UnknownAccess => org_id.is_none(),
+                    };
+                    let release = self.data.create_release(&pub_req, org_id, public, c)?;
+                    // The first publisher of a library is its owner
+                    let new_owner = NewLibraryOwner {
+                        lib_id: release.lib_id,
+                        user_id: user.user_id,
+                    };
+                    self.data.create_library_owner(&new_owner, c)?;
                     library = Some(release);
                 }
             }
 
-            // self.storage.save(pub_req.clone()).map_err(|e| {
-            //     error!("{:?}", e);
-            //     diesel::result::Error::RollbackTransaction
-            // })?;
-
             let new_version = NewVersion {
                 library_id: library.as_ref().unwrap().lib_id,
                 version_number: &pub_req.version,
+                published_by: Some(user.user_id),
+                // Verified against the uploaded files by `verify_upload`
+                checksum: pub_req.metadata.get("checksum").map(String::as_str),
             };
 
-            let version = self.data.create_version(&new_version, c).map_err(|e| {
-                error!("{:?}", e);
-                diesel::result::Error::RollbackTransaction
-            })?;
+            let version = self.data.create_version(&new_version, c)?;
 
-            for (dep, lib) in pub_req.dependencies.into_iter().enumerate() {
-                let dep_lib = self.data.get_release(&lib.0, None, None, c).map_err(|e| {
+            for (dep, lib) in pub_req.dependencies.iter().enumerate() {
+                let dep_lib = self.data.get_release(lib.0, None, None, c).map_err(|e| {
                     error!("Dependency {} for {}, not found: {}", dep, pub_req.name, e);
                     diesel::result::Error::RollbackTransaction
                 })?;
@@ -286,7 +844,7 @@ impl registry_service_server::RegistryService for RegistryService {
                 let new_dep = NewDependency {
                     version_id: version.id,
                     dependent_version_id: dep_lib.unwrap().1.pop().unwrap().id,
-                    dependency_range: &pub_req.version,
+                    dependency_range: lib.1,
                 };
                 let deps = self.data.create_dependency(&new_dep, c).map_err(|e| {
                     error!("{:?}", e);
@@ -301,13 +859,37 @@ impl registry_service_server::RegistryService for RegistryService {
 
             debug!("{:?}", version);
 
-            // TODO: Add deps
-
-            Ok(library)
+            Ok::<_, PublishError>((library.unwrap(), version.id))
         });
+        drop(conn);
+
+        // Files are only committed once the release is recorded, and the release is reverted
+        // when they can't be, so neither outlives the other
+        if let Ok((lib, version_id)) = &transaction {
+            let (token, name, version) = (
+                upload_token.clone(),
+                pub_req.name.clone(),
+                pub_req.version.clone(),
+            );
+            if let Err(e) = self
+                .with_storage(move |storage| storage.commit(&token, &name, &version))
+                .await
+            {
+                error!("failed to commit {}: {:?}", upload_token, e);
+                self.revert_release(lib, *version_id, previous_public).await;
+                self.discard_upload(&upload_token).await;
+                return Err(Status::internal(format!(
+                    "failed to store the release files: {:?}",
+                    e
+                )));
+            }
+        }
+        // A published upload was moved out of the staging area, and can't be published again
+        self.discard_upload(&upload_token).await;
 
         match transaction {
-            Err(Error::DatabaseError(kind, info)) => match kind {
+            Err(PublishError::Denied(reason)) => Err(Status::permission_denied(reason)),
+            Err(PublishError::Database(Error::DatabaseError(kind, info))) => match kind {
                 DatabaseErrorKind::UniqueViolation => {
                     warn!("{:?}", info);
                     Err(Status::already_exists(
@@ -320,23 +902,267 @@ impl registry_service_server::RegistryService for RegistryService {
                     kind
                 ))),
             },
-            Err(e) => Err(Status::internal(format!(
+            Err(PublishError::Database(e)) => Err(Status::internal(format!(
                 "some error occurred during db session: {:?}",
                 e
             ))),
-            Ok(r) => {
-                let lib = r.unwrap();
+            Ok((lib, _)) => {
                 info!("Uploaded {:?}", lib.name);
 
                 Ok(Response::new(()))
             }
         }
     }
+
+    async fn add_owner(&self, request: Request<OwnerRequest>) -> Result<Response<()>, Status> {
+        let user = require_caller(&self.data, request.extensions(), "manage owners")
+            .await?
+            .require(Scope::Admin)?;
+        let owner_req = request.into_inner();
+        info!(
+            "add owner {} to lib: {}",
+            owner_req.username, owner_req.library
+        );
+
+        let lib = self.find_library(&owner_req.library).await?;
+        if let Err(e) = self.require_owner(&lib, user).await {
+            // Libraries released before owners were tracked get their first one from an admin
+            let ownerless = self
+                .data
+                .get_library_owners(lib.lib_id)
+                .await
+                .map_err(|e| {
+                    Status::internal(format!("failed to fetch {} owners: {:?}", lib.name, e))
+                })?
+                .is_empty();
+            if !ownerless || !is_registry_admin(&self.data, &self.admins, user).await? {
+                return Err(e);
+            }
+        }
+        let new_owner = self.find_user(&owner_req.username).await?;
+
+        self.data
+            .add_library_owner(&NewLibraryOwner {
+                lib_id: lib.lib_id,
+                user_id: new_owner.user_id,
+            })
+            .await
+            .map_err(|e| Status::internal(format!("failed to add owner: {:?}", e)))?;
+        Ok(Response::new(()))
+    }
+
+    async fn remove_owner(&self, request: Request<OwnerRequest>) -> Result<Response<()>, Status> {
+        let user = require_caller(&self.data, request.extensions(), "manage owners")
+            .await?
+            .require(Scope::Admin)?;
+        let owner_req = request.into_inner();
+        info!(
+            "remove owner {} from lib: {}",
+            owner_req.username, owner_req.library
+        );
+
+        let lib = self.find_library(&owner_req.library).await?;
+        let owners = self.require_owner(&lib, user).await?;
+        let removed = owners
+            .iter()
+            .find(|o| o.username == owner_req.username)
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "{} is not an owner of {}",
+                    owner_req.username, lib.name
+                ))
+            })?;
+        if owners.len() == 1 {
+            return Err(Status::failed_precondition(format!(
+                "can't remove the last owner of {}",
+                lib.name
+            )));
+        }
+
+        self.data
+            .remove_library_owner(lib.lib_id, removed.user_id)
+            .await
+            .map_err(|e| Status::internal(format!("failed to remove owner: {:?}", e)))?;
+        Ok(Response::new(()))
+    }
+
+    async fn list_owners(
+        &self,
+        request: Request<ListOwnersRequest>,
+    ) -> Result<Response<ListOwnersResponse>, Status> {
+        let user = reader(&self.data, request.extensions()).await?;
+        let list_req = request.into_inner();
+
+        let lib = self.find_library(&list_req.library).await?;
+        if !self.can_read(&lib, user).await? {
+            return Err(Status::not_found(format!(
+                "library not found: {}",
+                lib.name
+            )));
+        }
+        let owners = self
+            .data
+            .get_library_owners(lib.lib_id)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("failed to fetch {} owners: {:?}", lib.name, e))
+            })?;
+
+        Ok(Response::new(ListOwnersResponse {
+            owners: owners.into_iter().map(|o| o.username).collect(),
+        }))
+    }
+
+    async fn yank(&self, request: Request<YankRequest>) -> Result<Response<()>, Status> {
+        let caller = require_caller(&self.data, request.extensions(), "yank releases").await?;
+        let yank_req = request.into_inner();
+        let user = caller.require(Scope::Publish(yank_req.library.clone()))?;
+        info!(
+            "{} lib: {}:{} by user {}",
+            if yank_req.undo { "unyank" } else { "yank" },
+            yank_req.library,
+            yank_req.version,
+            user.user_id
+        );
+
+        let lib = self.find_library(&yank_req.library).await?;
+        self.require_maintainer(&lib, user).await?;
+        let version = self
+            .data
+            .get_version_for_lib(&lib.name, &yank_req.version)
+            .await
+            .map_err(|e| {
+                Status::internal(format!(
+                    "failed to fetch library: {}:{} - {:?}",
+                    lib.name, yank_req.version, e
+                ))
+            })?
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "library release not found: {}:{}",
+                    lib.name, yank_req.version
+                ))
+            })?;
+
+        let reason = Some(yank_req.reason.as_str()).filter(|r| !yank_req.undo && !r.is_empty());
+        self.data
+            .set_version_yanked(version.id, !yank_req.undo, reason)
+            .await
+            .map_err(|e| {
+                Status::internal(format!(
+                    "failed to update {}:{}: {:?}",
+                    lib.name, yank_req.version, e
+                ))
+            })?;
+        Ok(Response::new(()))
+    }
+
+    async fn deprecate(&self, request: Request<DeprecateRequest>) -> Result<Response<()>, Status> {
+        let caller =
+            require_caller(&self.data, request.extensions(), "deprecate libraries").await?;
+        let dep_req = request.into_inner();
+        let user = caller.require(Scope::Publish(dep_req.library.clone()))?;
+        let version_range = dep_req.version_range.trim();
+        info!(
+            "{} lib: {}@{} by user {}",
+            if dep_req.undo {
+                "undeprecate"
+            } else {
+                "deprecate"
+            },
+            dep_req.library,
+            if version_range.is_empty() {
+                "*"
+            } else {
+                version_range
+            },
+            user.user_id
+        );
+
+        if !versioning::is_valid_requirement(version_range) {
+            return Err(Status::invalid_argument(format!(
+                "invalid version range: {}",
+                version_range
+            )));
+        }
+        let lib = self.find_library(&dep_req.library).await?;
+        self.require_maintainer(&lib, user).await?;
+
+        if dep_req.undo {
+            let removed = self
+                .data
+                .remove_library_deprecation(lib.lib_id, version_range)
+                .await
+                .map_err(|e| Status::internal(format!("failed to update {}: {:?}", lib.name, e)))?;
+            if removed == 0 {
+                return Err(Status::not_found(format!(
+                    "{} has no deprecation for this range",
+                    lib.name
+                )));
+            }
+            return Ok(Response::new(()));
+        }
+
+        let message = dep_req.message.trim();
+        if message.is_empty() {
+            return Err(Status::invalid_argument(
+                "a deprecation message is required",
+            ));
+        }
+        let replacement = dep_req.replacement.trim();
+        if !replacement.is_empty() {
+            if replacement == lib.name {
+                return Err(Status::invalid_argument(
+                    "a library can't be its own replacement",
+                ));
+            }
+            // Don't point users at libraries they can't see
+            let target = self.find_library(replacement).await?;
+            if !self.can_read(&target, Some(user)).await? {
+                return Err(Status::not_found(format!(
+                    "library not found: {}",
+                    replacement
+                )));
+            }
+        }
+
+        self.data
+            .set_library_deprecation(&NewLibraryDeprecation {
+                lib_id: lib.lib_id,
+                version_range,
+                message,
+                replacement: Some(replacement).filter(|r| !r.is_empty()),
+            })
+            .await
+            .map_err(|e| Status::internal(format!("failed to update {}: {:?}", lib.name, e)))?;
+        Ok(Response::new(()))
+    }
+}
+
+/// Where a release is published to, as resolved by [`RegistryService::authorize_publish`]
+struct PublishTarget {
+    release: Option<(models::Library, Vec<models::Version>)>,
+    org_id: Option<i32>,
+}
+
+/// Failures of the publish transaction, rolling back the release
+enum PublishError {
+    Denied(String),
+    Database(Error),
+}
+
+impl From<Error> for PublishError {
+    fn from(e: Error) -> Self {
+        PublishError::Database(e)
+    }
 }
 
 #[derive(Clone)]
 pub struct UserService {
     pub(crate) data: QueryLayer,
+    pub(crate) jwt: Arc<JwtKeys>,
+    pub(crate) signup_enabled: bool,
+    pub(crate) admins: Arc<Vec<String>>,
 }
 
 #[async_trait]
@@ -345,25 +1171,56 @@ impl user_service_server::UserService for UserService {
         &self,
         request: Request<CreateUserRequest>,
     ) -> Result<Response<User>, Status> {
+        if !self.signup_enabled {
+            // Closed registries still let their admins provision accounts
+            let provisioned = match authenticate(&self.data, request.extensions()).await? {
+                Some(caller) if caller.allows(&Scope::Admin) => {
+                    is_registry_admin(&self.data, &self.admins, caller.user).await?
+                }
+                _ => false,
+            };
+            if !provisioned {
+                return Err(Status::permission_denied(
+                    "signup is disabled on this registry, ask its admin for an account",
+                ));
+            }
+        }
         let mut u = request.into_inner();
         info!("create user: {}", u.username.clone());
+        registry_auth::validate_signup(&u.username, &u.email, &u.password)
+            .map_err(Status::invalid_argument)?;
         u.password = auth::Argon2Helper::hash_password(&u.password)
             .map_err(|err| Status::internal(format!("failed to hash user password: {}", err)))?;
 
-        let new_db_user = self
-            .data
-            .create_user(&u)
-            .await
-            .map_err(|err| Status::internal(format!("failed to create new user: {:?}", err)))?;
-
-        let response = plm_core::User {
-            username: u.username,
-            ..Default::default()
+        let new_db_user = match self.data.create_user(&u).await {
+            Ok(user) => user,
+            Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, info)) => {
+                return Err(match info.constraint_name() {
+                    Some(constraint) if constraint.contains("email") => {
+                        Status::already_exists(format!("email {} is already registered", u.email))
+                    }
+                    _ => {
+                        Status::already_exists(format!("username {} is already taken", u.username))
+                    }
+                })
+            }
+            Err(err) => {
+                return Err(Status::internal(format!(
+                    "failed to create new user: {:?}",
+                    err
+                )))
+            }
         };
 
-        // response.created_at = Some(timestamp);
         debug!("{:?}: {}", new_db_user.user_id, new_db_user.username);
-        Ok(Response::new(response))
+        Ok(Response::new(plm_core::User {
+            user_id: new_db_user.user_id.to_string(),
+            username: new_db_user.username,
+            email: new_db_user.email.unwrap_or_default(),
+            created_at: new_db_user.created_at.map(to_timestamp),
+            updated_at: new_db_user.updated_at.map(to_timestamp),
+            ..Default::default()
+        }))
     }
 
     async fn login(
@@ -381,16 +1238,16 @@ impl user_service_server::UserService for UserService {
         })?;
         match user {
             Some(u) => {
-                info!("fetched password: {}", u.password_hash);
-                let verify = auth::Argon2Helper::verify_password(login_req.token, u.password_hash)
-                    .map_err(|e| {
-                        Status::internal(format!(
-                            "failed to verify user {} password - {:?}",
-                            login_req.username, e
-                        ))
-                    })?;
+                let verify =
+                    auth::Argon2Helper::verify_password(login_req.password, u.password_hash)
+                        .map_err(|e| {
+                            Status::internal(format!(
+                                "failed to verify user {} password - {:?}",
+                                login_req.username, e
+                            ))
+                        })?;
                 if verify {
-                    match crate::auth::create_jwt_token(SECRET.as_bytes(), &u.user_id) {
+                    match crate::auth::create_jwt_token(&self.jwt, &u.user_id) {
                         Err(e) => Err(Status::internal(format!("failed to generate token: {}", e))),
                         Ok(jwt) => {
                             let res = LoginResponse { token: jwt };
@@ -398,13 +1255,622 @@ impl user_service_server::UserService for UserService {
                         }
                     }
                 } else {
-                    Err(Status::unauthenticated("invalid password"))
+                    Err(Status::unauthenticated("invalid username or password"))
                 }
             }
-            None => Err(Status::not_found("username not exists")),
+            None => Err(Status::unauthenticated("invalid username or password")),
         }
     }
+
+    async fn create_token(
+        &self,
+        request: Request<CreateTokenRequest>,
+    ) -> Result<Response<CreateTokenResponse>, Status> {
+        let user = require_caller(&self.data, request.extensions(), "create API tokens")
+            .await?
+            .require(Scope::Admin)?;
+        let token_req = request.into_inner();
+        info!(
+            "create API token {} for user {}",
+            token_req.name, user.user_id
+        );
+
+        if token_req.name.is_empty() {
+            return Err(Status::invalid_argument("must specify the token name"));
+        }
+        if token_req.scopes.is_empty() {
+            return Err(Status::invalid_argument(
+                "must grant the token at least one scope",
+            ));
+        }
+        let scopes = token_req
+            .scopes
+            .iter()
+            .map(|scope| scope.parse::<Scope>().map(|scope| scope.to_string()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Status::invalid_argument)?;
+        let expires_at = match token_req.expires_in {
+            0 => None,
+            secs => {
+                let secs = i64::try_from(secs)
+                    .map_err(|_| Status::invalid_argument("token expiry is out of range"))?;
+                Some(chrono::Utc::now().naive_utc() + chrono::Duration::seconds(secs))
+            }
+        };
+
+        let secret = registry_auth::generate_api_token();
+        let token_hash = hash_content(secret.as_bytes());
+        let new_token = NewApiToken {
+            user_id: user.user_id,
+            name: &token_req.name,
+            token_hash: &token_hash,
+            scopes,
+            expires_at,
+        };
+        let token = match self.data.create_api_token(&new_token).await {
+            Ok(token) => token,
+            Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                return Err(Status::already_exists(format!(
+                    "a token named {} already exists",
+                    token_req.name
+                )))
+            }
+            Err(e) => {
+                return Err(Status::internal(format!(
+                    "failed to create API token: {:?}",
+                    e
+                )))
+            }
+        };
+
+        Ok(Response::new(CreateTokenResponse {
+            token: Some(api_token_to_proto(token)),
+            secret,
+        }))
+    }
+
+    async fn list_tokens(
+        &self,
+        request: Request<()>,
+    ) -> Result<Response<ListTokensResponse>, Status> {
+        let user = require_caller(&self.data, request.extensions(), "list API tokens")
+            .await?
+            .require(Scope::Admin)?;
+
+        let tokens = self
+            .data
+            .get_api_tokens(user.user_id)
+            .await
+            .map_err(|e| Status::internal(format!("failed to fetch API tokens: {:?}", e)))?;
+        Ok(Response::new(ListTokensResponse {
+            tokens: tokens.into_iter().map(api_token_to_proto).collect(),
+        }))
+    }
+
+    async fn revoke_token(
+        &self,
+        request: Request<RevokeTokenRequest>,
+    ) -> Result<Response<()>, Status> {
+        let user = require_caller(&self.data, request.extensions(), "revoke API tokens")
+            .await?
+            .require(Scope::Admin)?;
+        let revoke_req = request.into_inner();
+        info!(
+            "revoke API token {} of user {}",
+            revoke_req.name, user.user_id
+        );
+
+        let removed = self
+            .data
+            .remove_api_token(user.user_id, &revoke_req.name)
+            .await
+            .map_err(|e| Status::internal(format!("failed to revoke API token: {:?}", e)))?;
+        if removed == 0 {
+            return Err(Status::not_found(format!(
+                "token not found: {}",
+                revoke_req.name
+            )));
+        }
+        Ok(Response::new(()))
+    }
     // async fn login(&self,request:Request<LoginRequest>) -> Result<Response<()> ,Status> {
     //     Ok(Response::new(());
     // }
 }
+
+#[derive(Clone)]
+pub struct OrganizationService {
+    pub(crate) data: QueryLayer,
+}
+
+impl OrganizationService {
+    /// Fetches an organization by its id or name
+    async fn find_organization(&self, org_ref: &str) -> Result<models::Organization, Status> {
+        let org = match org_ref.parse::<i32>() {
+            Ok(org_id) => self.data.get_organization(org_id).await,
+            Err(_) => self.data.get_organization_by_name(org_ref).await,
+        };
+        org.map_err(|e| {
+            Status::internal(format!("failed to fetch organization {}: {:?}", org_ref, e))
+        })?
+        .ok_or_else(|| Status::not_found(format!("organization not found: {}", org_ref)))
+    }
+
+    /// Fetches a user by its id or username
+    async fn find_user(&self, user_ref: &str) -> Result<models::User, Status> {
+        let user = match user_ref.parse::<i32>() {
+            Ok(user_id) => self.data.find_user_by_id(user_id).await.optional(),
+            Err(_) => self.data.get_user(user_ref).await,
+        };
+        user.map_err(|e| Status::internal(format!("failed to fetch user {}: {:?}", user_ref, e)))?
+            .ok_or_else(|| Status::not_found(format!("user not found: {}", user_ref)))
+    }
+
+    /// Fetches the requesting user membership, which must be granted at least `role`
+    async fn require_role(
+        &self,
+        org: &models::Organization,
+        user: AuthUser,
+        role: Role,
+    ) -> Result<models::UserOrganization, Status> {
+        let membership = self
+            .data
+            .get_membership(org.org_id, user.user_id)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("failed to fetch {} membership: {:?}", org.name, e))
+            })?;
+        match membership {
+            Some(m) if role_allows(m.role, role) => Ok(m),
+            Some(_) => Err(Status::permission_denied(format!(
+                "must be {} of {}",
+                role.as_str_name().to_lowercase(),
+                org.name
+            ))),
+            None => Err(Status::permission_denied(format!(
+                "must be a member of {}",
+                org.name
+            ))),
+        }
+    }
+
+    /// Fails when `user_id` is the only admin of the organization, which would leave it
+    /// unmanageable
+    async fn ensure_other_admin(
+        &self,
+        org: &models::Organization,
+        user_id: i32,
+    ) -> Result<(), Status> {
+        let members = self.load_members(org).await?;
+        let other_admin = members
+            .iter()
+            .any(|(u, m)| u.user_id != user_id && m.role == Role::Admin as i32);
+        if other_admin {
+            Ok(())
+        } else {
+            Err(Status::failed_precondition(format!(
+                "can't leave {} without an admin",
+                org.name
+            )))
+        }
+    }
+
+    async fn load_members(
+        &self,
+        org: &models::Organization,
+    ) -> Result<Vec<(models::User, models::UserOrganization)>, Status> {
+        self.data
+            .get_organization_members(org.org_id)
+            .await
+            .map_err(|e| Status::internal(format!("failed to fetch {} members: {:?}", org.name, e)))
+    }
+}
+
+#[async_trait]
+impl organization_service_server::OrganizationService for OrganizationService {
+    async fn get_organization(
+        &self,
+        request: Request<GetOrganizationRequest>,
+    ) -> Result<Response<GetOrganizationResponse>, Status> {
+        let user = require_caller(&self.data, request.extensions(), "view organizations")
+            .await?
+            .require(Scope::Read)?;
+        let get_req = request.into_inner();
+
+        let org = self.find_organization(&get_req.org_id).await?;
+        self.require_role(&org, user, Role::Contributor).await?;
+        let members = self.load_members(&org).await?;
+
+        Ok(Response::new(GetOrganizationResponse {
+            organization: Some(organization_to_proto(org, members)),
+        }))
+    }
+
+    async fn create_organization(
+        &self,
+        request: Request<CreateOrganizationRequest>,
+    ) -> Result<Response<Organization>, Status> {
+        let user = require_caller(&self.data, request.extensions(), "create organizations")
+            .await?
+            .require(Scope::Admin)?;
+        let create_req = request.into_inner();
+        info!("create organization: {}", create_req.name);
+
+        if create_req.name.is_empty()
+            || !create_req
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphabetic() || c == '-' || c == '_')
+        {
+            return Err(Status::invalid_argument(
+                "organization name must consist of ASCII alphabetic characters, dash, or lower dash",
+            ));
+        }
+
+        let new_org = NewOrganization {
+            name: &create_req.name,
+        };
+        let org = match self
+            .data
+            .create_organization(&new_org, user.user_id, Role::Admin as i32)
+            .await
+        {
+            Ok(org) => org,
+            Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                return Err(Status::already_exists(format!(
+                    "organization {} already exists",
+                    create_req.name
+                )))
+            }
+            Err(e) => {
+                return Err(Status::internal(format!(
+                    "failed to create organization: {:?}",
+                    e
+                )))
+            }
+        };
+        let members = self.load_members(&org).await?;
+
+        Ok(Response::new(organization_to_proto(org, members)))
+    }
+
+    async fn remove_organization(
+        &self,
+        request: Request<RemoveOrganizationRequest>,
+    ) -> Result<Response<()>, Status> {
+        let user = require_caller(&self.data, request.extensions(), "remove organizations")
+            .await?
+            .require(Scope::Admin)?;
+        let remove_req = request.into_inner();
+
+        let org = self.find_organization(&remove_req.org_id).await?;
+        self.require_role(&org, user, Role::Admin).await?;
+        info!("remove organization: {}", org.name);
+
+        let libraries = self
+            .data
+            .count_organization_libraries(org.org_id)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("failed to fetch {} libraries: {:?}", org.name, e))
+            })?;
+        if libraries > 0 {
+            return Err(Status::failed_precondition(format!(
+                "{} still has {} libraries published under its scope",
+                org.name, libraries
+            )));
+        }
+
+        self.data
+            .remove_organization(org.org_id)
+            .await
+            .map_err(|e| Status::internal(format!("failed to remove organization: {:?}", e)))?;
+        Ok(Response::new(()))
+    }
+
+    async fn add_user(&self, request: Request<AddUserRequest>) -> Result<Response<()>, Status> {
+        let user = require_caller(&self.data, request.extensions(), "manage organizations")
+            .await?
+            .require(Scope::Admin)?;
+        let add_req = request.into_inner();
+
+        let org = self.find_organization(&add_req.org_id).await?;
+        self.require_role(&org, user, Role::Admin).await?;
+        let member = self.find_user(&add_req.user_id).await?;
+        let role = match add_req.role() {
+            Role::UnknownRole => Role::Contributor,
+            role => role,
+        };
+        info!(
+            "add user {} to organization {} as {:?}",
+            member.username, org.name, role
+        );
+
+        let membership = NewUserOrganization {
+            user_id: member.user_id,
+            org_id: org.org_id,
+            role: role as i32,
+        };
+        match self.data.add_organization_member(&membership).await {
+            Ok(_) => Ok(Response::new(())),
+            Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                Err(Status::already_exists(format!(
+                    "{} is already a member of {}",
+                    member.username, org.name
+                )))
+            }
+            Err(e) => Err(Status::internal(format!(
+                "failed to add organization member: {:?}",
+                e
+            ))),
+        }
+    }
+
+    async fn remove_user(
+        &self,
+        request: Request<RemoveUserRequest>,
+    ) -> Result<Response<()>, Status> {
+        let user = require_caller(&self.data, request.extensions(), "manage organizations")
+            .await?
+            .require(Scope::Admin)?;
+        let remove_req = request.into_inner();
+
+        let org = self.find_organization(&remove_req.org_id).await?;
+        let member = self.find_user(&remove_req.user_id).await?;
+        // Members may always leave on their own
+        let required = if member.user_id == user.user_id {
+            Role::Contributor
+        } else {
+            Role::Admin
+        };
+        self.require_role(&org, user, required).await?;
+        self.ensure_other_admin(&org, member.user_id).await?;
+        info!(
+            "remove user {} from organization {}",
+            member.username, org.name
+        );
+
+        let removed = self
+            .data
+            .remove_organization_member(org.org_id, member.user_id)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("failed to remove organization member: {:?}", e))
+            })?;
+        if removed == 0 {
+            return Err(Status::not_found(format!(
+                "{} is not a member of {}",
+                member.username, org.name
+            )));
+        }
+        Ok(Response::new(()))
+    }
+
+    async fn update_user_role(
+        &self,
+        request: Request<UpdateUserRoleRequest>,
+    ) -> Result<Response<()>, Status> {
+        let user = require_caller(&self.data, request.extensions(), "manage organizations")
+            .await?
+            .require(Scope::Admin)?;
+        let update_req = request.into_inner();
+        let role = update_req.role();
+        if role == Role::UnknownRole {
+            return Err(Status::invalid_argument(
+                "role must be either ADMIN or CONTRIBUTOR",
+            ));
+        }
+
+        let org = self.find_organization(&update_req.org_id).await?;
+        self.require_role(&org, user, Role::Admin).await?;
+        let member = self.find_user(&update_req.user_id).await?;
+        if role != Role::Admin {
+            self.ensure_other_admin(&org, member.user_id).await?;
+        }
+        info!(
+            "update user {} role in organization {} to {:?}",
+            member.username, org.name, role
+        );
+
+        let updated = self
+            .data
+            .update_organization_member_role(org.org_id, member.user_id, role as i32)
+            .await
+            .map_err(|e| Status::internal(format!("failed to update member role: {:?}", e)))?;
+        if updated == 0 {
+            return Err(Status::not_found(format!(
+                "{} is not a member of {}",
+                member.username, org.name
+            )));
+        }
+        Ok(Response::new(()))
+    }
+}
+
+/// Whether a membership `granted` role covers the `required` one, admins are granted every role
+pub(crate) fn role_allows(granted: i32, required: Role) -> bool {
+    granted == Role::Admin as i32 || granted == required as i32
+}
+
+fn organization_to_proto(
+    org: models::Organization,
+    members: Vec<(models::User, models::UserOrganization)>,
+) -> Organization {
+    Organization {
+        org_id: org.org_id.to_string(),
+        name: org.name,
+        users: members
+            .into_iter()
+            .map(|(u, m)| User {
+                user_id: u.user_id.to_string(),
+                username: u.username,
+                email: u.email.unwrap_or_default(),
+                role: m.role,
+                ..Default::default()
+            })
+            .collect(),
+        created_at: org.created_at.map(to_timestamp),
+        updated_at: org.updated_at.map(to_timestamp),
+    }
+}
+
+fn api_token_to_proto(token: models::ApiToken) -> ApiToken {
+    ApiToken {
+        name: token.name,
+        scopes: token.scopes,
+        created_at: Some(to_timestamp(token.created_at)),
+        expires_at: token.expires_at.map(to_timestamp),
+        last_used_at: token.last_used_at.map(to_timestamp),
+    }
+}
+
+fn to_timestamp(t: chrono::NaiveDateTime) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: t.timestamp(),
+        nanos: t.timestamp_subsec_nanos() as i32,
+    }
+}
+
+/// The user a request was authenticated as, along with the scopes of the API token it was sent
+/// with, login sessions aren't restricted to any scope
+#[derive(Debug, Clone)]
+pub(crate) struct Caller {
+    pub(crate) user: AuthUser,
+    scopes: Option<Vec<Scope>>,
+}
+
+impl Caller {
+    fn allows(&self, scope: &Scope) -> bool {
+        match &self.scopes {
+            None => true,
+            Some(scopes) => scopes.iter().any(|s| s.allows(scope)),
+        }
+    }
+
+    /// The caller user, as long as it was granted `scope`
+    #[allow(clippy::result_large_err)]
+    fn require(&self, scope: Scope) -> Result<AuthUser, Status> {
+        if self.allows(&scope) {
+            Ok(self.user)
+        } else {
+            Err(Status::permission_denied(format!(
+                "API token is missing the {} scope",
+                scope
+            )))
+        }
+    }
+}
+
+/// Resolves the caller of a request from the credentials attached by the registry auth
+/// interceptor, `None` for anonymous requests
+async fn authenticate(
+    data: &QueryLayer,
+    extensions: &tonic::Extensions,
+) -> Result<Option<Caller>, Status> {
+    if let Some(user) = extensions.get::<AuthUser>() {
+        return Ok(Some(Caller {
+            user: *user,
+            scopes: None,
+        }));
+    }
+    let Some(credential) = extensions.get::<ApiTokenCredential>() else {
+        return Ok(None);
+    };
+
+    let token = data
+        .get_api_token_by_hash(&credential.token_hash)
+        .await
+        .map_err(|e| Status::internal(format!("failed to fetch API token: {:?}", e)))?
+        .ok_or_else(|| Status::unauthenticated("No valid auth token"))?;
+    let now = chrono::Utc::now().naive_utc();
+    if token.expires_at.is_some_and(|expires_at| expires_at <= now) {
+        return Err(Status::unauthenticated(format!(
+            "API token {} has expired",
+            token.name
+        )));
+    }
+    if let Err(e) = data.touch_api_token(token.id, now).await {
+        warn!("failed to record API token {} usage: {:?}", token.id, e);
+    }
+
+    let scopes = token
+        .scopes
+        .iter()
+        .filter_map(|scope| match scope.parse::<Scope>() {
+            Ok(scope) => Some(scope),
+            Err(e) => {
+                warn!("ignoring API token {} scope: {}", token.id, e);
+                None
+            }
+        })
+        .collect();
+    Ok(Some(Caller {
+        user: AuthUser {
+            user_id: token.user_id,
+        },
+        scopes: Some(scopes),
+    }))
+}
+
+/// The authenticated caller of a request that requires one, to perform `action`
+async fn require_caller(
+    data: &QueryLayer,
+    extensions: &tonic::Extensions,
+    action: &str,
+) -> Result<Caller, Status> {
+    authenticate(data, extensions)
+        .await?
+        .ok_or_else(|| Status::unauthenticated(format!("must be logged in to {}", action)))
+}
+
+/// Whether `user` is one of the registry admins listed in the `auth.admins` config
+async fn is_registry_admin(
+    data: &QueryLayer,
+    admins: &[String],
+    user: AuthUser,
+) -> Result<bool, Status> {
+    if admins.is_empty() {
+        return Ok(false);
+    }
+    let account = data
+        .find_user_by_id(user.user_id)
+        .await
+        .map_err(|e| Status::internal(format!("failed to fetch user: {:?}", e)))?;
+    Ok(admins.contains(&account.username))
+}
+
+/// The user reading private libraries, API tokens without the `read` scope read anonymously
+async fn reader(
+    data: &QueryLayer,
+    extensions: &tonic::Extensions,
+) -> Result<Option<AuthUser>, Status> {
+    Ok(authenticate(data, extensions)
+        .await?
+        .filter(|caller| caller.allows(&Scope::Read))
+        .map(|caller| caller.user))
+}
+
+/// The organization scope of a `@org/name` library name
+#[allow(clippy::result_large_err)]
+fn parse_scope(lib_name: &str) -> Result<Option<&str>, Status> {
+    let Some(scoped) = lib_name.strip_prefix('@') else {
+        return Ok(None);
+    };
+    match scoped.split_once('/') {
+        Some((org, name)) if !org.is_empty() && !name.is_empty() && !name.contains('/') => {
+            Ok(Some(org))
+        }
+        _ => Err(Status::invalid_argument(format!(
+            "invalid scoped library name: {}, should be: @<org>/<name>",
+            lib_name
+        ))),
+    }
+}
+
+/// Splits a release reference `<name>[:<version>]`, scoped names included (`@org/lib:1.2.3`)
+fn parse_release_ref(full: &str) -> (&str, Option<&str>) {
+    match full.rsplit_once(':') {
+        Some((name, version)) if !name.is_empty() && !version.is_empty() => (name, Some(version)),
+        _ => (full, None),
+    }
+}
@@ -22,7 +22,7 @@ use diesel::prelude::*;
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct NewUser<'a> {
     pub username: &'a str,
-    // pub email: &'a str,
+    pub email: &'a str,
     pub password_hash: &'a str,
     // pub created_at: NaiveDateTime,
     // pub updated_at: NaiveDateTime,
@@ -40,18 +40,24 @@ pub struct User {
     pub updated_at: Option<NaiveDateTime>,
 }
 
-#[derive(Debug, Queryable, Identifiable)]
+#[derive(Debug, Selectable, Queryable, Identifiable)]
 #[diesel(primary_key(org_id))]
 #[diesel(table_name = crate::data::schema::organizations)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct Organization {
     pub org_id: i32,
     pub name: String,
-    pub created_at: NaiveDateTime,
-    pub updated_at: NaiveDateTime,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
 }
 
-#[derive(Debug, Queryable, Identifiable, Associations)]
+#[derive(Insertable)]
+#[diesel(table_name = crate::data::schema::organizations)]
+pub struct NewOrganization<'a> {
+    pub name: &'a str,
+}
+
+#[derive(Debug, Selectable, Queryable, Identifiable, Associations)]
 #[diesel(belongs_to(User, foreign_key = user_id))]
 #[diesel(belongs_to(Organization, foreign_key = org_id))]
 #[diesel(primary_key(user_id, org_id))]
@@ -63,6 +69,14 @@ pub struct UserOrganization {
     pub role: i32,
 }
 
+#[derive(Insertable)]
+#[diesel(table_name = crate::data::schema::user_organizations)]
+pub struct NewUserOrganization {
+    pub user_id: i32,
+    pub org_id: i32,
+    pub role: i32,
+}
+
 #[derive(Debug, Selectable, Queryable, Identifiable)]
 #[diesel(primary_key(lib_id))]
 #[diesel(table_name = crate::data::schema::libraries)]
@@ -87,6 +101,47 @@ pub struct NewLibrary<'a> {
     pub description: Option<&'a str>,
 }
 
+#[derive(Debug, Queryable, Identifiable, Associations)]
+#[diesel(belongs_to(Library, foreign_key = lib_id))]
+#[diesel(belongs_to(User, foreign_key = user_id))]
+#[diesel(primary_key(lib_id, user_id))]
+#[diesel(table_name = crate::data::schema::library_owners)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct LibraryOwner {
+    pub lib_id: i32,
+    pub user_id: i32,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::data::schema::library_owners)]
+pub struct NewLibraryOwner {
+    pub lib_id: i32,
+    pub user_id: i32,
+}
+
+#[derive(Debug, Selectable, Queryable, Identifiable, Associations)]
+#[diesel(belongs_to(Library, foreign_key = lib_id))]
+#[diesel(table_name = crate::data::schema::library_deprecations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct LibraryDeprecation {
+    pub id: i32,
+    pub lib_id: i32,
+    pub version_range: String,
+    pub message: String,
+    pub replacement: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::data::schema::library_deprecations)]
+pub struct NewLibraryDeprecation<'a> {
+    pub lib_id: i32,
+    pub version_range: &'a str,
+    pub message: &'a str,
+    pub replacement: Option<&'a str>,
+}
+
 #[derive(Debug, QueryableByName)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct LatestVersion {
@@ -105,6 +160,10 @@ pub struct Version {
     pub library_id: i32,
     pub version_number: String,
     pub created_at: NaiveDateTime,
+    pub published_by: Option<i32>,
+    pub yanked: bool,
+    pub yank_reason: Option<String>,
+    pub checksum: Option<String>,
 }
 
 #[derive(Queryable, Associations)]
@@ -123,6 +182,8 @@ pub struct Dependency {
 pub struct NewVersion<'a> {
     pub library_id: i32,
     pub version_number: &'a str,
+    pub published_by: Option<i32>,
+    pub checksum: Option<&'a str>,
 }
 
 #[derive(Insertable)]
@@ -132,3 +193,48 @@ pub struct NewDependency<'a> {
     pub dependent_version_id: i32,
     pub dependency_range: &'a str,
 }
+
+#[derive(Debug, Selectable, Queryable, Identifiable, Associations)]
+#[diesel(belongs_to(User, foreign_key = user_id))]
+#[diesel(table_name = crate::data::schema::api_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ApiToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Selectable, Queryable, Identifiable, Associations)]
+#[diesel(belongs_to(User, foreign_key = user_id))]
+#[diesel(primary_key(token))]
+#[diesel(table_name = crate::data::schema::staged_uploads)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct StagedUpload {
+    pub token: String,
+    pub user_id: i32,
+    pub library: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::data::schema::staged_uploads)]
+pub struct NewStagedUpload<'a> {
+    pub token: &'a str,
+    pub user_id: i32,
+    pub library: &'a str,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::data::schema::api_tokens)]
+pub struct NewApiToken<'a> {
+    pub user_id: i32,
+    pub name: &'a str,
+    pub token_hash: &'a str,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<NaiveDateTime>,
+}
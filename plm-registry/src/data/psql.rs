@@ -15,7 +15,10 @@
 use crate::{diesel_migrations, models::Library};
 use diesel::prelude::*;
 use diesel::sql_query;
-use diesel::{pg::PgConnection, sql_types::Text};
+use diesel::{
+    pg::{data_types::PgInterval, PgConnection},
+    sql_types::Text,
+};
 use std::ops::DerefMut;
 use tokio::sync::Mutex;
 use tracing::debug;
@@ -25,7 +28,9 @@ use std::env;
 use std::sync::Arc;
 
 use crate::models::{
-    Dependency, LatestVersion, NewDependency, NewLibrary, NewUser, NewVersion, User, Version,
+    ApiToken, Dependency, LatestVersion, LibraryDeprecation, NewApiToken, NewDependency,
+    NewLibrary, NewLibraryDeprecation, NewLibraryOwner, NewOrganization, NewStagedUpload, NewUser,
+    NewUserOrganization, NewVersion, Organization, StagedUpload, User, UserOrganization, Version,
 };
 
 type QueryResult<T> = Result<T, diesel::result::Error>;
@@ -70,6 +75,7 @@ impl QueryLayer {
     pub async fn create_user(&self, user: &plm_core::CreateUserRequest) -> QueryResult<User> {
         let new_user = NewUser {
             username: &user.username,
+            email: &user.email,
             password_hash: &user.password,
         };
         let mut c = self.conn.lock().await;
@@ -87,8 +93,349 @@ impl QueryLayer {
             .first(c.deref_mut())
     }
 
+    // API tokens queries
+
+    pub async fn create_api_token(&self, new_token: &NewApiToken<'_>) -> QueryResult<ApiToken> {
+        let mut c = self.conn.lock().await;
+        diesel::insert_into(crate::schema::api_tokens::table)
+            .values(new_token)
+            .returning(ApiToken::as_returning())
+            .get_result(c.deref_mut())
+    }
+
+    // Staged uploads queries
+
+    pub async fn create_staged_upload(&self, upload: &NewStagedUpload<'_>) -> QueryResult<usize> {
+        let mut c = self.conn.lock().await;
+        diesel::insert_into(crate::schema::staged_uploads::table)
+            .values(upload)
+            .execute(c.deref_mut())
+    }
+
+    pub async fn get_staged_upload(&self, token: &str) -> QueryResult<Option<StagedUpload>> {
+        let mut c = self.conn.lock().await;
+        crate::schema::staged_uploads::table
+            .find(token)
+            .select(StagedUpload::as_select())
+            .first(c.deref_mut())
+            .optional()
+    }
+
+    /// Uploads staged longer than `ttl` ago, compared on the database clock which set `created_at`
+    pub async fn get_expired_staged_uploads(
+        &self,
+        ttl: PgInterval,
+    ) -> QueryResult<Vec<StagedUpload>> {
+        use crate::schema::staged_uploads;
+        let mut c = self.conn.lock().await;
+        staged_uploads::table
+            .filter(staged_uploads::created_at.lt(diesel::dsl::now - ttl))
+            .select(StagedUpload::as_select())
+            .load(c.deref_mut())
+    }
+
+    pub async fn remove_staged_upload(&self, token: &str) -> QueryResult<usize> {
+        let mut c = self.conn.lock().await;
+        diesel::delete(crate::schema::staged_uploads::table.find(token)).execute(c.deref_mut())
+    }
+
+    pub async fn get_api_token_by_hash(&self, hash: &str) -> QueryResult<Option<ApiToken>> {
+        use crate::schema::api_tokens;
+        let mut c = self.conn.lock().await;
+
+        api_tokens::table
+            .filter(api_tokens::token_hash.eq(hash))
+            .select(ApiToken::as_select())
+            .first(c.deref_mut())
+            .optional()
+    }
+
+    pub async fn get_api_tokens(&self, user_id: i32) -> QueryResult<Vec<ApiToken>> {
+        use crate::schema::api_tokens;
+        let mut c = self.conn.lock().await;
+
+        api_tokens::table
+            .filter(api_tokens::user_id.eq(user_id))
+            .order(api_tokens::created_at)
+            .select(ApiToken::as_select())
+            .load(c.deref_mut())
+    }
+
+    pub async fn remove_api_token(&self, user_id: i32, token_name: &str) -> QueryResult<usize> {
+        use crate::schema::api_tokens;
+        let mut c = self.conn.lock().await;
+
+        diesel::delete(
+            api_tokens::table
+                .filter(api_tokens::user_id.eq(user_id))
+                .filter(api_tokens::name.eq(token_name)),
+        )
+        .execute(c.deref_mut())
+    }
+
+    /// Record the last time an API token authenticated a request
+    pub async fn touch_api_token(
+        &self,
+        token_id: i32,
+        used_at: chrono::NaiveDateTime,
+    ) -> QueryResult<usize> {
+        use crate::schema::api_tokens;
+        let mut c = self.conn.lock().await;
+
+        diesel::update(api_tokens::table.find(token_id))
+            .set(api_tokens::last_used_at.eq(used_at))
+            .execute(c.deref_mut())
+    }
+
+    // Organizations queries
+
+    /// Create an organization with `admin_id` as its first admin
+    pub async fn create_organization(
+        &self,
+        new_org: &NewOrganization<'_>,
+        admin_id: i32,
+        admin_role: i32,
+    ) -> QueryResult<Organization> {
+        let mut c = self.conn.lock().await;
+        c.build_transaction().run(|conn| {
+            let org = diesel::insert_into(crate::schema::organizations::table)
+                .values(new_org)
+                .returning(Organization::as_returning())
+                .get_result(conn)?;
+            diesel::insert_into(crate::schema::user_organizations::table)
+                .values(&NewUserOrganization {
+                    user_id: admin_id,
+                    org_id: org.org_id,
+                    role: admin_role,
+                })
+                .execute(conn)?;
+            Ok(org)
+        })
+    }
+
+    pub async fn get_organization(&self, org_id: i32) -> QueryResult<Option<Organization>> {
+        use crate::schema::organizations;
+        let mut c = self.conn.lock().await;
+
+        organizations::table
+            .find(org_id)
+            .select(Organization::as_select())
+            .first(c.deref_mut())
+            .optional()
+    }
+
+    pub async fn get_organization_by_name(
+        &self,
+        org_name: &str,
+    ) -> QueryResult<Option<Organization>> {
+        use crate::schema::organizations;
+        let mut c = self.conn.lock().await;
+
+        organizations::table
+            .filter(organizations::name.eq(org_name))
+            .select(Organization::as_select())
+            .first(c.deref_mut())
+            .optional()
+    }
+
+    /// Remove an organization along with its memberships
+    pub async fn remove_organization(&self, org_id: i32) -> QueryResult<usize> {
+        use crate::schema::{organizations, user_organizations};
+        let mut c = self.conn.lock().await;
+
+        c.build_transaction().run(|conn| {
+            diesel::delete(user_organizations::table.filter(user_organizations::org_id.eq(org_id)))
+                .execute(conn)?;
+            diesel::delete(organizations::table.find(org_id)).execute(conn)
+        })
+    }
+
+    /// Retrieve the members of an organization along with their membership
+    pub async fn get_organization_members(
+        &self,
+        org_id: i32,
+    ) -> QueryResult<Vec<(User, UserOrganization)>> {
+        use crate::schema::{user_organizations, users};
+        let mut c = self.conn.lock().await;
+
+        user_organizations::table
+            .inner_join(users::table)
+            .filter(user_organizations::org_id.eq(org_id))
+            .order(users::username)
+            .select((User::as_select(), UserOrganization::as_select()))
+            .load(c.deref_mut())
+    }
+
+    pub async fn get_membership(
+        &self,
+        org_id: i32,
+        user_id: i32,
+    ) -> QueryResult<Option<UserOrganization>> {
+        use crate::schema::user_organizations;
+        let mut c = self.conn.lock().await;
+
+        user_organizations::table
+            .find((user_id, org_id))
+            .select(UserOrganization::as_select())
+            .first(c.deref_mut())
+            .optional()
+    }
+
+    pub async fn add_organization_member(
+        &self,
+        membership: &NewUserOrganization,
+    ) -> QueryResult<usize> {
+        let mut c = self.conn.lock().await;
+        diesel::insert_into(crate::schema::user_organizations::table)
+            .values(membership)
+            .execute(c.deref_mut())
+    }
+
+    pub async fn remove_organization_member(
+        &self,
+        org_id: i32,
+        user_id: i32,
+    ) -> QueryResult<usize> {
+        use crate::schema::user_organizations;
+        let mut c = self.conn.lock().await;
+
+        diesel::delete(user_organizations::table.find((user_id, org_id))).execute(c.deref_mut())
+    }
+
+    pub async fn update_organization_member_role(
+        &self,
+        org_id: i32,
+        user_id: i32,
+        new_role: i32,
+    ) -> QueryResult<usize> {
+        use crate::schema::user_organizations;
+        let mut c = self.conn.lock().await;
+
+        diesel::update(user_organizations::table.find((user_id, org_id)))
+            .set(user_organizations::role.eq(new_role))
+            .execute(c.deref_mut())
+    }
+
+    /// Count the libraries published under an organization scope
+    pub async fn count_organization_libraries(&self, org_id: i32) -> QueryResult<i64> {
+        use crate::schema::libraries;
+        let mut c = self.conn.lock().await;
+
+        libraries::table
+            .filter(libraries::org_id.eq(org_id))
+            .count()
+            .get_result(c.deref_mut())
+    }
+
     // Libraries queries
 
+    /// Grant a user publish permissions on a library, a no-op for existing owners
+    pub fn create_library_owner(
+        &self,
+        new_owner: &NewLibraryOwner,
+        conn: &mut PgConnection,
+    ) -> QueryResult<usize> {
+        diesel::insert_into(crate::schema::library_owners::table)
+            .values(new_owner)
+            .on_conflict_do_nothing()
+            .execute(conn)
+    }
+
+    pub async fn add_library_owner(&self, new_owner: &NewLibraryOwner) -> QueryResult<usize> {
+        let mut c = self.conn.lock().await;
+        self.create_library_owner(new_owner, c.deref_mut())
+    }
+
+    pub async fn remove_library_owner(&self, lib_id: i32, user_id: i32) -> QueryResult<usize> {
+        use crate::schema::library_owners;
+
+        let mut c = self.conn.lock().await;
+        diesel::delete(library_owners::table.find((lib_id, user_id))).execute(c.deref_mut())
+    }
+
+    /// Retrieve the users allowed to publish a library, in the order they were granted
+    /// Fetch the user ids of the owners of a library, locking them until the end of the
+    /// transaction so they can't be removed meanwhile
+    pub fn lock_library_owners(
+        &self,
+        lib_id: i32,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Vec<i32>> {
+        use crate::schema::library_owners;
+
+        library_owners::table
+            .filter(library_owners::lib_id.eq(lib_id))
+            .select(library_owners::user_id)
+            .for_update()
+            .load(conn)
+    }
+
+    pub async fn get_library_owners(&self, lib_id: i32) -> QueryResult<Vec<User>> {
+        use crate::schema::{library_owners, users};
+
+        let mut c = self.conn.lock().await;
+        library_owners::table
+            .inner_join(users::table)
+            .filter(library_owners::lib_id.eq(lib_id))
+            .order(library_owners::created_at)
+            .select(User::as_select())
+            .load(c.deref_mut())
+    }
+
+    // Library deprecations queries
+
+    /// Deprecates a version range of a library, replacing the notice of that exact range if any
+    pub async fn set_library_deprecation(
+        &self,
+        deprecation: &NewLibraryDeprecation<'_>,
+    ) -> QueryResult<usize> {
+        use crate::schema::library_deprecations;
+        let mut c = self.conn.lock().await;
+
+        diesel::insert_into(library_deprecations::table)
+            .values(deprecation)
+            .on_conflict((
+                library_deprecations::lib_id,
+                library_deprecations::version_range,
+            ))
+            .do_update()
+            .set((
+                library_deprecations::message.eq(deprecation.message),
+                library_deprecations::replacement.eq(deprecation.replacement),
+            ))
+            .execute(c.deref_mut())
+    }
+
+    pub async fn remove_library_deprecation(
+        &self,
+        lib_id: i32,
+        version_range: &str,
+    ) -> QueryResult<usize> {
+        use crate::schema::library_deprecations;
+        let mut c = self.conn.lock().await;
+
+        diesel::delete(
+            library_deprecations::table
+                .filter(library_deprecations::lib_id.eq(lib_id))
+                .filter(library_deprecations::version_range.eq(version_range)),
+        )
+        .execute(c.deref_mut())
+    }
+
+    pub async fn get_library_deprecations(
+        &self,
+        lib_id: i32,
+    ) -> QueryResult<Vec<LibraryDeprecation>> {
+        use crate::schema::library_deprecations;
+        let mut c = self.conn.lock().await;
+
+        library_deprecations::table
+            .filter(library_deprecations::lib_id.eq(lib_id))
+            .order(library_deprecations::created_at)
+            .select(LibraryDeprecation::as_select())
+            .load(c.deref_mut())
+    }
+
     /// Create a new version entry
     pub fn create_version(
         &self,
@@ -103,10 +450,40 @@ impl QueryLayer {
                 versions::library_id,
                 versions::version_number,
                 versions::created_at,
+                versions::published_by,
+                versions::yanked,
+                versions::yank_reason,
+                versions::checksum,
             ))
             .get_result(conn)
     }
 
+    /// Delete a version entry with its dependencies
+    pub fn remove_version(&self, version_id: i32, conn: &mut PgConnection) -> QueryResult<usize> {
+        use crate::schema::{dependencies, versions};
+
+        diesel::delete(dependencies::table.filter(dependencies::version_id.eq(version_id)))
+            .execute(conn)?;
+        diesel::delete(versions::table.find(version_id)).execute(conn)
+    }
+
+    /// Yanks a version with an optional reason, or restores it when `yanked` is false
+    pub async fn set_version_yanked(
+        &self,
+        version_id: i32,
+        yanked: bool,
+        reason: Option<&str>,
+    ) -> QueryResult<usize> {
+        use crate::schema::versions;
+        let mut c = self.conn.lock().await;
+        diesel::update(versions::table.find(version_id))
+            .set((
+                versions::yanked.eq(yanked),
+                versions::yank_reason.eq(reason),
+            ))
+            .execute(c.deref_mut())
+    }
+
     /// Fetch library metadata by dependency ID
     pub async fn get_library_by_dependency(&self, dep_id: i32) -> QueryResult<Option<Library>> {
         use crate::schema::{dependencies, libraries, versions};
@@ -237,17 +614,42 @@ impl QueryLayer {
             .load::<Dependency>(conn)
     }
 
+    /// Make a library readable by anyone, or only by its owners and organization
+    pub fn set_library_access(
+        &self,
+        lib_id: i32,
+        is_public: bool,
+        conn: &mut PgConnection,
+    ) -> QueryResult<Library> {
+        use crate::schema::libraries;
+
+        diesel::update(libraries::table.find(lib_id))
+            .set((
+                libraries::public.eq(is_public),
+                libraries::updated_at.eq(diesel::dsl::now),
+            ))
+            .returning(Library::as_returning())
+            .get_result(conn)
+    }
+
+    /// Delete a library without versions, along with its owners and deprecations
+    pub fn remove_library(&self, lib_id: i32, conn: &mut PgConnection) -> QueryResult<usize> {
+        diesel::delete(crate::schema::libraries::table.find(lib_id)).execute(conn)
+    }
+
     pub fn create_release(
         &self,
         release: &plm_core::Library,
+        org_id: Option<i32>,
+        public: bool,
         conn: &mut PgConnection,
     ) -> QueryResult<Library> {
         let default_desc = "".to_string();
         let desc = release.metadata.get("description").unwrap_or(&default_desc);
         let new_release = NewLibrary {
             name: &release.name,
-            org_id: None,
-            public: false,
+            org_id: org_id.as_ref(),
+            public,
             description: Some(desc),
         };
         // let mut c = self.conn.lock().await;
@@ -317,10 +719,28 @@ impl QueryLayer {
         }
     }
 
+    /// Fetch a single version row of a library by its version number
+    pub async fn get_version_for_lib(
+        &self,
+        lib_name: &str,
+        version_number: &str,
+    ) -> QueryResult<Option<Version>> {
+        use crate::schema::{libraries, versions};
+        let mut c = self.conn.lock().await;
+
+        versions::table
+            .inner_join(libraries::table)
+            .filter(libraries::name.eq(lib_name))
+            .filter(versions::version_number.eq(version_number))
+            .select(Version::as_select())
+            .first(c.deref_mut())
+            .optional()
+    }
+
     pub async fn get_async_release(
         &self,
         lib_name: &str,
-        _lib_version: Option<i32>,
+        lib_version: Option<i32>,
         _lib_scope: Option<String>,
     ) -> QueryResult<Option<(Library, Vec<Version>)>> {
         use crate::schema::{libraries::dsl::*, versions};
@@ -337,10 +757,14 @@ impl QueryLayer {
         {
             println!("{:?}", library);
             // Now query for versions and dependencies based on the found library
-            let related_versions = versions::table
+            let mut versions_query = versions::table
                 .filter(versions::library_id.eq(library.lib_id))
-                .load::<Version>(c.deref_mut())?;
-            println!("{:?}", related_versions);
+                .into_boxed();
+            if let Some(version_id) = lib_version {
+                versions_query = versions_query.filter(versions::id.eq(version_id));
+            }
+            let related_versions = versions_query.load::<Version>(c.deref_mut())?;
+            debug!("{:?}", related_versions);
             // let related_dependencies = Dependency::belonging_to(&related_versions)
             //     .load::<Dependency>(c.deref_mut())?
             //     .grouped_by(&related_versions);
@@ -1,5 +1,20 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    api_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 255]
+        name -> Varchar,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        scopes -> Array<Text>,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     dependencies (id) {
         id -> Int4,
@@ -23,6 +38,27 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    library_deprecations (id) {
+        id -> Int4,
+        lib_id -> Int4,
+        #[max_length = 255]
+        version_range -> Varchar,
+        message -> Text,
+        #[max_length = 255]
+        replacement -> Nullable<Varchar>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    library_owners (lib_id, user_id) {
+        lib_id -> Int4,
+        user_id -> Int4,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     organizations (org_id) {
         org_id -> Int4,
@@ -33,10 +69,22 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    staged_uploads (token) {
+        #[max_length = 36]
+        token -> Varchar,
+        user_id -> Int4,
+        #[max_length = 255]
+        library -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     user_organizations (user_id, org_id) {
         user_id -> Int4,
         org_id -> Int4,
+        role -> Int4,
     }
 }
 
@@ -61,18 +109,33 @@ diesel::table! {
         #[max_length = 50]
         version_number -> Varchar,
         created_at -> Timestamp,
+        published_by -> Nullable<Int4>,
+        yanked -> Bool,
+        yank_reason -> Nullable<Text>,
+        #[max_length = 64]
+        checksum -> Nullable<Varchar>,
     }
 }
 
+diesel::joinable!(api_tokens -> users (user_id));
 diesel::joinable!(libraries -> organizations (org_id));
+diesel::joinable!(library_deprecations -> libraries (lib_id));
+diesel::joinable!(library_owners -> libraries (lib_id));
+diesel::joinable!(library_owners -> users (user_id));
+diesel::joinable!(staged_uploads -> users (user_id));
 diesel::joinable!(user_organizations -> organizations (org_id));
 diesel::joinable!(user_organizations -> users (user_id));
 diesel::joinable!(versions -> libraries (library_id));
+diesel::joinable!(versions -> users (published_by));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    api_tokens,
     dependencies,
     libraries,
+    library_deprecations,
+    library_owners,
     organizations,
+    staged_uploads,
     user_organizations,
     users,
     versions,
@@ -14,14 +14,42 @@
 
 #[macro_use]
 extern crate diesel_migrations;
-extern crate lazy_static;
 
 pub mod storage {
 
+    /// Stores the release files. Implementations may block, the registry service runs them on the
+    /// blocking thread pool
     pub trait RegistryStorage: Send + Sync {
         fn save(&self, library: plm_core::Library) -> RegistryResult<()>;
         fn load(&self, library: &str) -> RegistryResult<Vec<File>>;
-        fn write(&self, upload: &UploadRequest) -> RegistryResult<()>;
+        /// Stages an uploaded file under an upload token, until it is committed or discarded
+        fn stage(&self, token: &str, file: &File) -> RegistryResult<()>;
+        /// Loads every file staged under an upload token
+        fn load_staged(&self, token: &str) -> RegistryResult<Vec<File>>;
+        /// Drops every file staged under an upload token
+        fn discard(&self, token: &str) -> RegistryResult<()>;
+
+        /// Saves the files staged under an upload token as the `name@version` release
+        fn commit(&self, token: &str, name: &str, version: &str) -> RegistryResult<()> {
+            let files = self.load_staged(token)?;
+            self.save(plm_core::Library {
+                name: name.to_string(),
+                version: version.to_string(),
+                packages: vec![plm_core::Package {
+                    files,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            })?;
+            self.discard(token)
+        }
+    }
+
+    /// Checks an upload token is a registry issued uuid, so it is safe to use in a storage path
+    pub(crate) fn staging_token(token: &str) -> RegistryResult<String> {
+        uuid::Uuid::parse_str(token)
+            .map(|token| token.to_string())
+            .map_err(|_| RegistryError::StorageError(format!("invalid upload token: {}", token)))
     }
 
     mod builder;
@@ -29,9 +57,9 @@ pub mod storage {
     pub mod s3;
 
     pub use builder::StorageBuilder;
-    use plm_core::plm::{package::v1::File, registry::v1::UploadRequest};
+    use plm_core::plm::package::v1::File;
 
-    use crate::types::RegistryResult;
+    use crate::{error::RegistryError, types::RegistryResult};
 }
 
 pub mod data {
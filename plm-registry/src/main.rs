@@ -14,10 +14,11 @@
 
 // Std libs
 use ::tracing::debug;
-use plm_core::Config;
+use plm_core::{plm::registry::v1::storage::StorageBackend, Config};
 use std::env;
 // Own libs
 use plm_registry::{
+    auth::JwtKeys,
     config::ConfigBuilder,
     psql::{establish_connection, initialize_schema},
     storage::RegistryStorage,
@@ -33,30 +34,19 @@ async fn main() -> RegistryResult<()> {
     let config = setup_configs(&mut configs)?;
     setup_tracing(&(config.clone().server.unwrap().log_level as u8));
     let mut storage = StorageBuilder::new();
-    #[allow(unused_assignments)]
-    let mut store_path = None;
-    match config.storage.unwrap().storage_backend {
-        None => store_path = Some("plm_registry".to_string()),
-        Some(storgae_backend) => match storgae_backend {
-            plm_core::plm::registry::v1::storage::StorageBackend::Local(local) => {
-                let path = local.registry_path.clone();
-                store_path = Some(path)
-            }
-            plm_core::plm::registry::v1::storage::StorageBackend::S3(_) => {
-                todo!()
-            }
-        },
-    }
-    let storage = setup_storage(&mut storage, &store_path.unwrap())?;
+    let storage = setup_storage(&mut storage, config.storage.unwrap().storage_backend)?;
 
     let mut db = DataBuilder::new();
     setup_db(&mut db).await?;
     dbg!(db);
 
-    let server_builder = RegistryServerBuilder::new(storage);
+    let auth = config.auth.unwrap();
+    let jwt = setup_auth(&auth)?;
+
+    let server_builder = RegistryServerBuilder::new(storage, jwt);
     let tmp_server_cfg = config.server.unwrap();
     let addr = format!("{}:{}", tmp_server_cfg.host, tmp_server_cfg.port);
-    let server = setup_server(&mut server_builder.clone(), addr)?;
+    let server = setup_server(&mut server_builder.clone(), addr, &auth)?;
 
     server.run().await;
 
@@ -93,13 +83,15 @@ fn setup_configs(cfg_builder: &mut ConfigBuilder) -> RegistryResult<Config> {
 
 fn setup_storage(
     storage_builder: &mut StorageBuilder,
-    store_path: &str,
+    storage_backend: Option<StorageBackend>,
 ) -> RegistryResult<Box<dyn RegistryStorage + Sync + Send>> {
     // TODO: Validate registry path for local storage / ping S3
     debug!("setting up storage");
 
-    let storage = storage_builder.with_store_path(store_path);
-    Ok(Box::new(storage.clone().build()))
+    if let Some(backend) = storage_backend {
+        storage_builder.with_backend(backend);
+    }
+    storage_builder.clone().build()
 }
 
 async fn setup_db(_db_builder: &mut DataBuilder) -> RegistryResult<()> {
@@ -110,11 +102,22 @@ async fn setup_db(_db_builder: &mut DataBuilder) -> RegistryResult<()> {
     Ok(())
 }
 
+fn setup_auth(auth: &plm_core::Auth) -> RegistryResult<JwtKeys> {
+    debug!("setting up auth");
+    JwtKeys::from_config(auth)
+}
+
 fn setup_server(
     server_builder: &mut RegistryServerBuilder,
     addr: String,
+    auth: &plm_core::Auth,
 ) -> RegistryResult<RegistryServer> {
-    Ok(server_builder.with_addr(addr).clone().build())
+    Ok(server_builder
+        .with_addr(addr)
+        .disable_signup(auth.disable_signup)
+        .with_admins(auth.admins.clone())
+        .clone()
+        .build())
 }
 
 // use diesel::prelude::*;
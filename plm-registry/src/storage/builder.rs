@@ -12,13 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use plm_core::Local;
+use plm_core::{plm::registry::v1::storage::StorageBackend, Local};
 
-use crate::local::LocalStorage;
+use crate::{local::LocalStorage, s3::S3Storage, types::RegistryResult, RegistryStorage};
 
 #[derive(Debug, Clone)]
 pub struct StorageBuilder {
-    store_path: String,
+    backend: StorageBackend,
 }
 
 impl Default for StorageBuilder {
@@ -30,21 +30,31 @@ impl Default for StorageBuilder {
 impl StorageBuilder {
     pub fn new() -> Self {
         Self {
-            store_path: "plm_registry".to_string(),
+            backend: StorageBackend::Local(Local {
+                registry_path: "plm_registry".to_string(),
+            }),
         }
     }
 
     pub fn with_store_path(&mut self, storage_path: &str) -> &mut Self {
-        self.store_path = storage_path.to_string();
+        self.backend = StorageBackend::Local(Local {
+            registry_path: storage_path.to_string(),
+        });
         self
     }
 
-    pub fn build(self) -> LocalStorage {
-        LocalStorage {
-            storage: Local {
-                registry_path: self.store_path.clone(),
-            },
-            registry_path: self.store_path.clone(),
+    pub fn with_backend(&mut self, backend: StorageBackend) -> &mut Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn build(self) -> RegistryResult<Box<dyn RegistryStorage + Sync + Send>> {
+        match self.backend {
+            StorageBackend::Local(local) => Ok(Box::new(LocalStorage {
+                registry_path: local.registry_path.clone(),
+                storage: local,
+            })),
+            StorageBackend::S3(s3) => Ok(Box::new(S3Storage::new(s3)?)),
         }
     }
 }
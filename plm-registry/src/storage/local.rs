@@ -13,15 +13,30 @@
 // limitations under the License.
 
 use plm_core::{
-    library::store::LibraryStore,
     plm::{package::v1::File, registry::v1::Local},
-    utils::fs,
+    utils::{fs, hash_content},
 };
-use std::{fs as _fs, str};
-use tracing::{debug, error, info};
+use std::{
+    collections::BTreeMap,
+    fs as _fs,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info};
+
+use crate::{error::RegistryError, storage::staging_token, types::RegistryResult, RegistryStorage};
 
-use crate::{error::RegistryError, types::RegistryResult, RegistryStorage};
+/// Directory holding the file contents, keyed by their SHA-256 digest
+const BLOBS_DIR: &str = "blobs";
+/// Per release index mapping each file path to its content digest
+const INDEX_FILE: &str = "index.json";
+/// Directory holding the uploaded files of releases not published yet
+const STAGING_DIR: &str = "staging";
 
+/// Stores libraries on the local file system.
+///
+/// File contents are stored once under `blobs/<digest[..2]>/<digest>`, and every release has an
+/// `<name>/<version>/index.json` mapping its file paths to their digests, so unchanged files are
+/// shared across versions and libraries.
 #[derive(Debug, Clone)]
 pub struct LocalStorage {
     pub(crate) storage: Local,
@@ -29,145 +44,266 @@ pub struct LocalStorage {
     pub(crate) registry_path: String,
 }
 
-impl RegistryStorage for LocalStorage {
-    fn load(&self, library: &str) -> RegistryResult<Vec<File>> {
-        let local_storage_path =
-            fs::FileSystem::join_paths(self.storage.registry_path.as_str(), library.clone());
-
-        // let protos_dir = fs::FileSystem::join_paths(self.storage.clone().registry_path, library);
-        println!("{:?}", local_storage_path);
-        let protos = LibraryStore::collect(&local_storage_path, local_storage_path.as_path(), &[])
-            .map_err(|e| {
-                RegistryError::InvalidConfigSetup(format!("unable to load protobuf files: {}", e))
-            })?;
+type ReleaseIndex = BTreeMap<String, String>;
 
-        println!("{:?}", protos);
+impl LocalStorage {
+    fn release_dir(&self, library_path: &str) -> PathBuf {
+        fs::FileSystem::join_paths(self.storage.registry_path.as_str(), library_path)
+    }
 
-        let mut files = Vec::with_capacity(protos.len());
-        for file in protos {
-            let file_path = fs::FileSystem::join_paths(local_storage_path.clone(), file.clone());
-            let file_name = file_path.clone();
-            let content = fs::FileSystem::read_file(file_name.to_str().unwrap()).map_err(|e| {
-                RegistryError::InvalidFileContent(format!("failed to load proto file: {:?}", e))
-            })?;
-            files.push(File {
-                name: file.to_string(),
-                content: content.into_bytes(),
-            })
+    fn blob_path(&self, digest: &str) -> PathBuf {
+        fs::FileSystem::join_paths(self.storage.registry_path.as_str(), BLOBS_DIR)
+            .join(&digest[..2])
+            .join(digest)
+    }
+
+    /// Writes a content blob unless an identical one is already stored, returning its digest
+    fn write_blob(&self, content: &[u8]) -> RegistryResult<String> {
+        let digest = hash_content(content);
+        let blob_path = self.blob_path(&digest);
+        if blob_path.is_file() {
+            debug!("blob already stored: {}", digest);
+            return Ok(digest);
         }
-        Ok(files)
+
+        let storage_error = |e| {
+            RegistryError::InvalidFileContent(format!("unable to write blob {}: {}", digest, e))
+        };
+        _fs::create_dir_all(blob_path.parent().unwrap()).map_err(storage_error)?;
+        // Write then rename, so a blob is never visible half written. Every writer has its own
+        // temporary file, as the same content may be published concurrently
+        let tmp_path = blob_path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        if let Err(e) = _fs::write(&tmp_path, content) {
+            _fs::remove_file(&tmp_path).ok();
+            return Err(storage_error(e));
+        }
+        if let Err(e) = _fs::rename(&tmp_path, &blob_path) {
+            _fs::remove_file(&tmp_path).ok();
+            // Stored meanwhile by another writer, with the very same content
+            if !blob_path.is_file() {
+                return Err(storage_error(e));
+            }
+        }
+        debug!("saved blob: {:?}", blob_path);
+        Ok(digest)
     }
 
-    fn write(&self, upload: &plm_core::plm::registry::v1::UploadRequest) -> RegistryResult<()> {
-        let lib_name_version: Vec<&str> = upload.library.split(':').collect();
-        let library_path = fs::FileSystem::join_paths(lib_name_version[0], lib_name_version[1]);
-        let local_storage_path =
-            fs::FileSystem::join_paths(self.storage.registry_path.as_str(), library_path.clone());
-        info!("saving local library: {:?}", local_storage_path);
-        fs::FileSystem::create_dir(local_storage_path.clone().to_str().unwrap()).map_err(|e| {
+    fn read_index(&self, library_path: &str) -> RegistryResult<Option<ReleaseIndex>> {
+        let index_path = self.release_dir(library_path).join(INDEX_FILE);
+        if !index_path.is_file() {
+            return Ok(None);
+        }
+        let index = fs::FileSystem::read_file(index_path.to_str().unwrap()).map_err(|e| {
+            RegistryError::InvalidFileContent(format!("unable to read {:?}: {}", index_path, e))
+        })?;
+        serde_json::from_str(&index).map(Some).map_err(|e| {
+            RegistryError::InvalidFileContent(format!(
+                "invalid release index {:?}: {}",
+                index_path, e
+            ))
+        })
+    }
+
+    fn write_index(&self, library_path: &str, index: &ReleaseIndex) -> RegistryResult<()> {
+        let release_dir = self.release_dir(library_path);
+        info!("saving local library: {:?}", release_dir);
+        fs::FileSystem::create_dir(release_dir.to_str().unwrap()).map_err(|e| {
             RegistryError::InvalidConfigSetup(format!(
                 "couldnt create directory for new library release {}",
                 e
             ))
         })?;
-        let file = &upload.file;
-        match file {
-            Some(f) => {
-                let file_path = fs::FileSystem::join_paths(
-                    local_storage_path.to_str().unwrap(),
-                    f.name.clone(),
-                );
-                match str::from_utf8(&f.content) {
-                    Ok(file_string_content) => {
-                        // Create directory if it doesn't exist
-                        let file_parent = file_path.parent().unwrap();
-                        if let Err(e) = _fs::create_dir_all(file_parent) {
-                            error!("Failed to create directory {:?}: {:?}", file_parent, e);
-                            return Err(RegistryError::InvalidFileContent(format!(
-                                "unable to create directory {:?}",
-                                file_parent
-                            )));
-                        }
-
-                        debug!("saving file to-> {:?}", file_path);
-                        fs::FileSystem::write_file(
-                            file_path.to_str().unwrap(),
-                            file_string_content,
-                        )
-                        .map_err(|e| {
-                            RegistryError::InvalidFileContent(format!(
-                                "unable to write {:?}/{}: {:?}",
-                                library_path, f.name, e
-                            ))
-                        })?;
-                    }
-                    Err(_) => error!("unable to write {:?}/{}", library_path, f.name),
-                }
-            }
-            None => {
-                return Err(RegistryError::InvalidFileContent(
-                    "File content is missing".to_string(),
+        fs::FileSystem::write_json(release_dir.join(INDEX_FILE).to_str().unwrap(), index).map_err(
+            |e| {
+                RegistryError::InvalidFileContent(format!(
+                    "unable to write {} index: {}",
+                    library_path, e
                 ))
-            }
+            },
+        )
+    }
+
+    fn staging_dir(&self, token: &str) -> RegistryResult<PathBuf> {
+        Ok(
+            fs::FileSystem::join_paths(self.storage.registry_path.as_str(), STAGING_DIR)
+                .join(staging_token(token)?),
+        )
+    }
+
+    /// Loads every file under a directory, named by their path relative to it
+    fn load_dir(&self, dir: &Path) -> RegistryResult<Vec<File>> {
+        let mut names = vec![];
+        list_files(dir, dir, &mut names).map_err(|e| {
+            RegistryError::InvalidConfigSetup(format!("unable to load library files: {}", e))
+        })?;
+        names.sort(); // to ensure determinism
+
+        let mut files = Vec::with_capacity(names.len());
+        for name in names {
+            let content = fs::FileSystem::read_binary_file(&dir.join(&name)).map_err(|e| {
+                RegistryError::InvalidFileContent(format!(
+                    "failed to load {}/{}: {}",
+                    dir.display(),
+                    name,
+                    e
+                ))
+            })?;
+            files.push(File { name, content })
         }
+        Ok(files)
+    }
+}
 
+// Recursively lists the files under `dir`, relative to `base`
+fn list_files(dir: &Path, base: &Path, files: &mut Vec<String>) -> std::io::Result<()> {
+    for entry in _fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            list_files(&path, base, files)?;
+        } else if let Ok(relative) = path.strip_prefix(base) {
+            files.push(relative.to_string_lossy().into_owned());
+        }
+    }
+    Ok(())
+}
+
+impl RegistryStorage for LocalStorage {
+    fn load(&self, library: &str) -> RegistryResult<Vec<File>> {
+        let index = match self.read_index(library)? {
+            Some(index) => index,
+            // Releases stored as plain files, before content addressing was introduced
+            None => return self.load_dir(&self.release_dir(library)),
+        };
+
+        let mut files = Vec::with_capacity(index.len());
+        for (name, digest) in index {
+            let content =
+                fs::FileSystem::read_binary_file(&self.blob_path(&digest)).map_err(|e| {
+                    RegistryError::InvalidFileContent(format!(
+                        "failed to load {}/{} ({}): {}",
+                        library, name, digest, e
+                    ))
+                })?;
+            files.push(File { name, content });
+        }
+        Ok(files)
+    }
+
+    fn stage(&self, token: &str, file: &File) -> RegistryResult<()> {
+        let file_path = self.staging_dir(token)?.join(&file.name);
+        let storage_error =
+            |e| RegistryError::StorageError(format!("unable to stage {}: {}", file.name, e));
+        _fs::create_dir_all(file_path.parent().unwrap()).map_err(storage_error)?;
+        _fs::write(&file_path, &file.content).map_err(storage_error)?;
+        debug!("staged file: {:?}", file_path);
         Ok(())
     }
 
-    fn save(&self, library: plm_core::Library) -> RegistryResult<()> {
-        let library_path = fs::FileSystem::join_paths(library.name, library.version);
-        let local_storage_path =
-            fs::FileSystem::join_paths(self.storage.registry_path.as_str(), library_path.clone());
-        info!("saving local library: {:?}", local_storage_path);
-        fs::FileSystem::create_dir(local_storage_path.clone().to_str().unwrap()).map_err(|e| {
-            RegistryError::InvalidConfigSetup(format!(
-                "couldnt create directory for new library release {}",
-                e
-            ))
-        })?;
-        for pkg in library.packages {
-            let pkg_dir = fs::FileSystem::join_paths(
-                local_storage_path.clone(),
-                pkg.name.as_str().replace('.', "/"),
-            );
-            let dir_path = pkg_dir.to_str().unwrap();
-            debug!("creating package dir: {}", dir_path);
-            // fs::FileSystem::create_dir(&dir_path)
-            //     .map_err(|e| RegistryError::InvalidFileContent(format!("failed to create directory: {}", dir_path)))?;
-            for file in pkg.files {
-                let file_path = fs::FileSystem::join_paths(
-                    local_storage_path.to_str().unwrap(),
-                    file.name.clone(),
-                );
-                match str::from_utf8(&file.content) {
-                    Ok(file_string_content) => {
-                        // Create directory if it doesn't exist
-                        let file_parent = file_path.parent().unwrap();
-                        if let Err(e) = _fs::create_dir_all(file_parent) {
-                            error!("Failed to create directory {:?}: {:?}", file_parent, e);
-                            return Err(RegistryError::InvalidFileContent(format!(
-                                "unable to create directory {:?}",
-                                file_parent
-                            )));
-                        }
-
-                        debug!("saving file to-> {:?}", file_path);
-                        fs::FileSystem::write_file(
-                            file_path.to_str().unwrap(),
-                            file_string_content,
-                        )
-                        .map_err(|e| {
-                            RegistryError::InvalidFileContent(format!(
-                                "unable to write {:?}/{}: {:?}",
-                                library_path, file.name, e
-                            ))
-                        })?;
-                    }
-                    Err(_) => error!("unable to write {:?}/{}", library_path, file.name),
-                }
-            }
+    fn load_staged(&self, token: &str) -> RegistryResult<Vec<File>> {
+        let staging_dir = self.staging_dir(token)?;
+        if !staging_dir.is_dir() {
+            return Err(RegistryError::StorageError(format!(
+                "unknown upload: {}",
+                token
+            )));
         }
+        self.load_dir(&staging_dir)
+    }
 
+    fn discard(&self, token: &str) -> RegistryResult<()> {
+        let staging_dir = self.staging_dir(token)?;
+        if staging_dir.is_dir() {
+            _fs::remove_dir_all(&staging_dir).map_err(|e| {
+                RegistryError::StorageError(format!("unable to discard upload {}: {}", token, e))
+            })?;
+        }
         Ok(())
     }
+
+    fn save(&self, library: plm_core::Library) -> RegistryResult<()> {
+        let library_path = format!("{}/{}", library.name, library.version);
+        let mut index = ReleaseIndex::new();
+        for file in library.packages.iter().flat_map(|pkg| pkg.files.iter()) {
+            let digest = self.write_blob(&file.content)?;
+            index.insert(file.name.clone(), digest);
+        }
+        self.write_index(&library_path, &index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_storage_binary_roundtrip() {
+        let registry_path = std::env::temp_dir().join(format!("plm-local-{}", std::process::id()));
+        let storage = LocalStorage {
+            storage: Local {
+                registry_path: registry_path.to_string_lossy().into_owned(),
+            },
+            registry_path: registry_path.to_string_lossy().into_owned(),
+        };
+
+        // A descriptor set is not valid UTF-8, and must round-trip untouched
+        let files = vec![
+            File {
+                name: "shop/v1/shop.pb".to_string(),
+                content: vec![0x0a, 0xff, 0xfe, 0x00, 0x80, 0x12],
+            },
+            File {
+                name: "shop/v1/shop.proto".to_string(),
+                content: b"syntax = \"proto3\";\n".to_vec(),
+            },
+        ];
+        let token = uuid::Uuid::new_v4().to_string();
+        for file in &files {
+            storage.stage(&token, file).unwrap();
+        }
+        // Tokens are joined onto the staging path, anything else than a uuid must be refused
+        assert!(storage.load_staged("../blobs").is_err());
+        assert!(storage.discard("..").is_err());
+        storage.commit(&token, "shop", "0.1.0").unwrap();
+        let loaded = storage.load("shop/0.1.0");
+        _fs::remove_dir_all(&registry_path).ok();
+
+        assert_eq!(loaded.unwrap(), files);
+    }
+
+    #[test]
+    fn test_local_storage_shares_blobs() {
+        let registry_path =
+            std::env::temp_dir().join(format!("plm-local-blobs-{}", std::process::id()));
+        let storage = LocalStorage {
+            storage: Local {
+                registry_path: registry_path.to_string_lossy().into_owned(),
+            },
+            registry_path: registry_path.to_string_lossy().into_owned(),
+        };
+
+        let file = File {
+            name: "shop/v1/shop.proto".to_string(),
+            content: b"syntax = \"proto3\";\n".to_vec(),
+        };
+        for version in ["0.1.0", "0.2.0"] {
+            storage
+                .save(plm_core::Library {
+                    name: "shop".to_string(),
+                    version: version.to_string(),
+                    packages: vec![plm_core::Package {
+                        files: vec![file.clone()],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let mut blobs = vec![];
+        list_files(&registry_path.join(BLOBS_DIR), &registry_path, &mut blobs).unwrap();
+        let loaded = storage.load("shop/0.2.0");
+        _fs::remove_dir_all(&registry_path).ok();
+
+        assert_eq!(blobs.len(), 1);
+        assert_eq!(loaded.unwrap(), vec![file]);
+    }
 }
@@ -11,3 +11,189 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+
+use plm_core::plm::{package::v1::File, registry::v1::S3};
+use s3::{creds::Credentials, Bucket, Region};
+use tracing::{debug, info};
+
+use crate::{error::RegistryError, storage::staging_token, types::RegistryResult, RegistryStorage};
+
+const DEFAULT_REGION: &str = "us-east-1";
+/// Key prefix of the uploaded files of releases not published yet
+const STAGING_PREFIX: &str = "staging";
+
+/// Stores libraries in an S3 (or S3-compatible) bucket, under `<name>/<version>/<file>` keys
+#[derive(Debug, Clone)]
+pub struct S3Storage {
+    pub(crate) storage: S3,
+    bucket: Bucket,
+}
+
+impl S3Storage {
+    pub fn new(storage: S3) -> RegistryResult<Self> {
+        let region_name = if storage.region.is_empty() {
+            DEFAULT_REGION.to_string()
+        } else {
+            storage.region.clone()
+        };
+        let region = if storage.endpoint.is_empty() {
+            region_name.parse::<Region>().map_err(|e| {
+                RegistryError::InvalidConfigSetup(format!("invalid s3 region: {}", e))
+            })?
+        } else {
+            Region::Custom {
+                region: region_name,
+                endpoint: storage.endpoint.clone(),
+            }
+        };
+
+        let credentials = if storage.access_key.is_empty() {
+            Credentials::default()
+        } else {
+            Credentials::new(
+                Some(&storage.access_key),
+                Some(&storage.secret_key),
+                None,
+                None,
+                None,
+            )
+        }
+        .map_err(|e| RegistryError::InvalidConfigSetup(format!("invalid s3 credentials: {}", e)))?;
+
+        let mut bucket = Bucket::new(&storage.bucket_name, region, credentials).map_err(|e| {
+            RegistryError::InvalidConfigSetup(format!(
+                "invalid s3 bucket {}: {}",
+                storage.bucket_name, e
+            ))
+        })?;
+        if storage.path_style {
+            bucket = bucket.with_path_style();
+        }
+
+        Ok(Self { storage, bucket })
+    }
+
+    fn list_keys(&self, prefix: &str) -> RegistryResult<Vec<String>> {
+        let listing = self.bucket.list(prefix.to_string(), None).map_err(|e| {
+            RegistryError::StorageError(format!("failed to list {}: {}", prefix, e))
+        })?;
+
+        let mut keys: Vec<String> = listing
+            .into_iter()
+            .flat_map(|page| page.contents)
+            .map(|object| object.key)
+            .collect();
+        keys.sort(); // to ensure determinism
+        Ok(keys)
+    }
+
+    /// Loads every object under `prefix`, named by their key relative to it
+    fn load_prefix(&self, prefix: &str) -> RegistryResult<Vec<File>> {
+        let keys = self.list_keys(prefix)?;
+        let mut files = Vec::with_capacity(keys.len());
+        for key in keys {
+            let object = self.bucket.get_object(&key).map_err(|e| {
+                RegistryError::StorageError(format!("failed to fetch {}: {}", key, e))
+            })?;
+            files.push(File {
+                name: key[prefix.len()..].to_string(),
+                content: object.to_vec(),
+            });
+        }
+        Ok(files)
+    }
+
+    fn staging_prefix(&self, token: &str) -> RegistryResult<String> {
+        Ok(format!("{}/{}/", STAGING_PREFIX, staging_token(token)?))
+    }
+
+    fn put(&self, key: &str, content: &[u8]) -> RegistryResult<()> {
+        debug!("uploading s3 object: {}", key);
+        self.bucket
+            .put_object(key, content)
+            .map_err(|e| RegistryError::StorageError(format!("failed to upload {}: {}", key, e)))?;
+        Ok(())
+    }
+}
+
+impl RegistryStorage for S3Storage {
+    fn load(&self, library: &str) -> RegistryResult<Vec<File>> {
+        self.load_prefix(&format!("{}/", library.trim_end_matches('/')))
+    }
+
+    fn stage(&self, token: &str, file: &File) -> RegistryResult<()> {
+        self.put(
+            &format!("{}{}", self.staging_prefix(token)?, file.name),
+            &file.content,
+        )
+    }
+
+    fn load_staged(&self, token: &str) -> RegistryResult<Vec<File>> {
+        self.load_prefix(&self.staging_prefix(token)?)
+    }
+
+    fn discard(&self, token: &str) -> RegistryResult<()> {
+        for key in self.list_keys(&self.staging_prefix(token)?)? {
+            self.bucket.delete_object(&key).map_err(|e| {
+                RegistryError::StorageError(format!("failed to delete {}: {}", key, e))
+            })?;
+        }
+        Ok(())
+    }
+
+    fn save(&self, library: plm_core::Library) -> RegistryResult<()> {
+        info!(
+            "saving s3 library: {}/{}/{}",
+            self.storage.bucket_name, library.name, library.version
+        );
+        for file in library.packages.iter().flat_map(|pkg| pkg.files.iter()) {
+            self.put(
+                &format!("{}/{}/{}", library.name, library.version, file.name),
+                &file.content,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use s3::BucketConfiguration;
+
+    use super::*;
+
+    /// Runs against a local S3-compatible service, e.g:
+    /// `docker run -p 9000:9000 minio/minio server /data`
+    #[test]
+    #[ignore = "requires an S3-compatible service, see PLM_S3_TEST_ENDPOINT"]
+    fn test_s3_storage_roundtrip() {
+        let config = S3 {
+            bucket_name: "plm-storage-test".to_string(),
+            endpoint: std::env::var("PLM_S3_TEST_ENDPOINT")
+                .unwrap_or_else(|_| "http://127.0.0.1:9000".to_string()),
+            access_key: "minioadmin".to_string(),
+            secret_key: "minioadmin".to_string(),
+            path_style: true,
+            ..Default::default()
+        };
+        let storage = S3Storage::new(config).unwrap();
+        // Best effort, the bucket may already exist
+        let _ = Bucket::create_with_path_style(
+            &storage.storage.bucket_name,
+            storage.bucket.region(),
+            Credentials::new(Some("minioadmin"), Some("minioadmin"), None, None, None).unwrap(),
+            BucketConfiguration::default(),
+        );
+
+        let file = File {
+            name: "shop/v1/shop.proto".to_string(),
+            content: b"syntax = \"proto3\";\npackage shop.v1;\n".to_vec(),
+        };
+        let token = uuid::Uuid::new_v4().to_string();
+        storage.stage(&token, &file).unwrap();
+        storage.commit(&token, "shop", "0.1.0").unwrap();
+
+        assert!(storage.load_staged(&token).unwrap().is_empty());
+        assert_eq!(storage.load("shop/0.1.0").unwrap(), vec![file]);
+    }
+}
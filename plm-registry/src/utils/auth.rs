@@ -12,19 +12,140 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    env, fmt, fs,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use plm_core::plm::registry::v1::auth::Algorithm as AuthAlgorithm;
 use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{error::RegistryError, types::RegistryResult};
+
+/// The HS256 secret used when none is configured, only accepted in dev mode
+const DEFAULT_SECRET: &str = "default_secret";
+/// Login tokens expire after 24 hours unless configured otherwise
+const DEFAULT_TOKEN_EXPIRY: u64 = 3600 * 24;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Claims {
     sub: String,
     exp: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    iss: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    aud: Option<String>,
+}
+
+/// The keys and claims login tokens are signed and validated with, loaded from the `auth`
+/// section of the registry config
+#[derive(Clone)]
+pub struct JwtKeys {
+    algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    expiry: u64,
+    issuer: Option<String>,
+    audience: Option<String>,
+}
+
+impl JwtKeys {
+    /// Loads the signing keys, refusing the default HS256 secret outside of dev mode
+    pub fn from_config(auth: &plm_core::Auth) -> RegistryResult<Self> {
+        let (algorithm, encoding, decoding) = match auth.algorithm() {
+            AuthAlgorithm::Hs256 => {
+                let secret = load_secret(auth)?;
+                (
+                    Algorithm::HS256,
+                    EncodingKey::from_secret(secret.as_bytes()),
+                    DecodingKey::from_secret(secret.as_bytes()),
+                )
+            }
+            AuthAlgorithm::Rs256 => {
+                let (private_key, public_key) = load_keypair(auth)?;
+                (
+                    Algorithm::RS256,
+                    EncodingKey::from_rsa_pem(&private_key).map_err(invalid_key)?,
+                    DecodingKey::from_rsa_pem(&public_key).map_err(invalid_key)?,
+                )
+            }
+            AuthAlgorithm::Eddsa => {
+                let (private_key, public_key) = load_keypair(auth)?;
+                (
+                    Algorithm::EdDSA,
+                    EncodingKey::from_ed_pem(&private_key).map_err(invalid_key)?,
+                    DecodingKey::from_ed_pem(&public_key).map_err(invalid_key)?,
+                )
+            }
+        };
+
+        Ok(Self {
+            algorithm,
+            encoding,
+            decoding,
+            expiry: match auth.token_expiry {
+                0 => DEFAULT_TOKEN_EXPIRY,
+                expiry => expiry,
+            },
+            issuer: Some(auth.issuer.clone()).filter(|iss| !iss.is_empty()),
+            audience: Some(auth.audience.clone()).filter(|aud| !aud.is_empty()),
+        })
+    }
+}
+
+fn load_secret(auth: &plm_core::Auth) -> RegistryResult<String> {
+    let secret = if !auth.secret_file.is_empty() {
+        let secret = fs::read_to_string(&auth.secret_file).map_err(|err| {
+            RegistryError::InvalidConfigSetup(format!(
+                "failed to read JWT secret file {}: {}",
+                auth.secret_file, err
+            ))
+        })?;
+        Some(secret.trim().to_string()).filter(|secret| !secret.is_empty())
+    } else {
+        env::var("PLM_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())
+    };
+
+    match secret {
+        Some(secret) if secret != DEFAULT_SECRET => Ok(secret),
+        _ if auth.dev_mode => {
+            warn!("signing login tokens with the default secret, never use dev mode in production");
+            Ok(DEFAULT_SECRET.to_string())
+        }
+        _ => Err(RegistryError::InvalidConfigSetup(
+            "refusing to start with the default JWT secret, set auth.secretFile or PLM_SECRET \
+             (or auth.devMode for local development)"
+                .to_string(),
+        )),
+    }
+}
+
+fn load_keypair(auth: &plm_core::Auth) -> RegistryResult<(Vec<u8>, Vec<u8>)> {
+    if auth.private_key_file.is_empty() || auth.public_key_file.is_empty() {
+        return Err(RegistryError::InvalidConfigSetup(format!(
+            "{} requires both auth.privateKeyFile and auth.publicKeyFile",
+            auth.algorithm().as_str_name()
+        )));
+    }
+    let read = |path: &str| {
+        fs::read(path).map_err(|err| {
+            RegistryError::InvalidConfigSetup(format!("failed to read key {}: {}", path, err))
+        })
+    };
+    Ok((read(&auth.private_key_file)?, read(&auth.public_key_file)?))
+}
+
+fn invalid_key(err: jsonwebtoken::errors::Error) -> RegistryError {
+    RegistryError::InvalidConfigSetup(format!("invalid JWT key: {}", err))
 }
 
 pub fn create_jwt_token(
-    secret: &[u8],
+    keys: &JwtKeys,
     user_id: &i32,
 ) -> Result<String, jsonwebtoken::errors::Error> {
     // Get current time in seconds since the Unix epoch
@@ -33,26 +154,228 @@ pub fn create_jwt_token(
         .duration_since(UNIX_EPOCH)
         .expect("Time went backwards");
 
-    // Set the token to expire in 24 hour (3600 seconds * 24)
-    let expiration: usize = (since_the_epoch.as_secs() + (3600 * 24))
+    let expiration: usize = (since_the_epoch.as_secs() + keys.expiry)
         .try_into()
         .unwrap();
     let claims = Claims {
         sub: format!("{}", user_id.to_owned()),
         exp: expiration,
+        iss: keys.issuer.clone(),
+        aud: keys.audience.clone(),
     };
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret),
-    )
+    encode(&Header::new(keys.algorithm), &claims, &keys.encoding)
+}
+
+/// The user a request was authenticated as, attached to the request extensions by the
+/// registry auth interceptor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuthUser {
+    pub user_id: i32,
 }
 
-pub fn validate_jwt_token(token: &str, secret: &[u8]) -> Result<(), jsonwebtoken::errors::Error> {
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(secret),
-        &Validation::default(),
-    )?;
+/// Validates a JWT token, including its issuer and audience when configured, and returns the
+/// user it was issued to (from its `sub` claim)
+pub fn validate_jwt_token(
+    token: &str,
+    keys: &JwtKeys,
+) -> Result<AuthUser, jsonwebtoken::errors::Error> {
+    let mut validation = Validation::new(keys.algorithm);
+    if let Some(issuer) = &keys.issuer {
+        validation.set_issuer(&[issuer]);
+    }
+    if let Some(audience) = &keys.audience {
+        validation.set_audience(&[audience]);
+    }
+
+    let data = decode::<Claims>(token, &keys.decoding, &validation)?;
+    let user_id = data
+        .claims
+        .sub
+        .parse()
+        .map_err(|_| jsonwebtoken::errors::ErrorKind::InvalidSubject)?;
+    Ok(AuthUser { user_id })
+}
+
+/// Minimum length of account passwords
+const MIN_PASSWORD_LENGTH: usize = 8;
+/// Longest accepted password, bounding the cost of hashing it
+const MAX_PASSWORD_LENGTH: usize = 128;
+
+/// Checks the username, email and password of a new account, returning why they're rejected
+pub fn validate_signup(username: &str, email: &str, password: &str) -> Result<(), String> {
+    // Usernames are also referenced by id in organization requests, so they can't be all digits
+    if username.is_empty()
+        || username.len() > 64
+        || !username.starts_with(|c: char| c.is_ascii_alphabetic())
+        || !username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "invalid username: {}, should start with a letter and only contain letters, digits, \
+             '-' or '_' (up to 64 characters)",
+            username
+        ));
+    }
+
+    let valid_email = match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && email.len() <= 255
+                && !email.chars().any(char::is_whitespace)
+        }
+        None => false,
+    };
+    if !valid_email {
+        return Err(format!("invalid email address: {}", email));
+    }
+
+    let length = password.chars().count();
+    if length < MIN_PASSWORD_LENGTH {
+        return Err(format!(
+            "password must be at least {} characters long",
+            MIN_PASSWORD_LENGTH
+        ));
+    }
+    if length > MAX_PASSWORD_LENGTH {
+        return Err(format!(
+            "password must be at most {} characters long",
+            MAX_PASSWORD_LENGTH
+        ));
+    }
+    if password.eq_ignore_ascii_case(username) {
+        return Err("password must not be the username".to_string());
+    }
     Ok(())
 }
+
+/// Prefix of API tokens, telling them apart from login JWTs
+pub const API_TOKEN_PREFIX: &str = "plm_";
+
+/// The hash of an API token a request was sent with, attached to the request extensions by the
+/// registry auth interceptor and resolved to its user by the service handlers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiTokenCredential {
+    pub token_hash: String,
+}
+
+/// Generates a new random API token
+pub fn generate_api_token() -> String {
+    format!(
+        "{}{}{}",
+        API_TOKEN_PREFIX,
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// A permission granted to an API token, login sessions are granted every scope
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    /// Read private libraries the user has access to
+    Read,
+    /// Upload and publish releases of a library
+    Publish(String),
+    /// Anything the user can do, including managing owners, organizations and tokens
+    Admin,
+}
+
+impl Scope {
+    /// Whether holding this scope grants the `required` one
+    pub fn allows(&self, required: &Scope) -> bool {
+        matches!(self, Scope::Admin) || self == required
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Read => write!(f, "read"),
+            Scope::Publish(lib) => write!(f, "publish:{}", lib),
+            Scope::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Scope::Read),
+            "admin" => Ok(Scope::Admin),
+            _ => match s.strip_prefix("publish:") {
+                Some(lib) if !lib.is_empty() => Ok(Scope::Publish(lib.to_string())),
+                _ => Err(format!(
+                    "invalid scope: {}, should be one of: read, publish:<library>, admin",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scopes() {
+        let scopes = ["read", "publish:@acme/payments", "admin"];
+        for scope in scopes {
+            assert_eq!(scope.parse::<Scope>().unwrap().to_string(), scope);
+        }
+        assert!("publish:".parse::<Scope>().is_err());
+        assert!("write".parse::<Scope>().is_err());
+
+        let publish = Scope::Publish("payments".to_string());
+        assert!(Scope::Admin.allows(&publish));
+        assert!(publish.allows(&publish));
+        assert!(!publish.allows(&Scope::Publish("orders".to_string())));
+        assert!(!Scope::Read.allows(&publish));
+    }
+
+    #[test]
+    fn test_validate_signup() {
+        assert!(validate_signup("alice", "alice@example.com", "correct horse").is_ok());
+        assert!(validate_signup("alice-ci_2", "a.b+c@mail.example.org", "12345678").is_ok());
+
+        assert!(validate_signup("", "alice@example.com", "correct horse").is_err());
+        assert!(validate_signup("42", "alice@example.com", "correct horse").is_err());
+        assert!(validate_signup("al ice", "alice@example.com", "correct horse").is_err());
+        assert!(validate_signup("alice", "alice", "correct horse").is_err());
+        assert!(validate_signup("alice", "alice@localhost", "correct horse").is_err());
+        assert!(validate_signup("alice", "@example.com", "correct horse").is_err());
+        assert!(validate_signup("alice", "alice@example.com", "short").is_err());
+        assert!(validate_signup("alicealice", "alice@example.com", "AliceAlice").is_err());
+    }
+
+    #[test]
+    fn test_jwt_claims() {
+        let secret_file = env::temp_dir().join(format!("plm-secret-{}", uuid::Uuid::new_v4()));
+        fs::write(&secret_file, "s3cr3t\n").unwrap();
+        let mut auth = plm_core::Auth {
+            secret_file: secret_file.to_str().unwrap().to_string(),
+            issuer: "plm-registry".to_string(),
+            audience: "plm".to_string(),
+            ..Default::default()
+        };
+        let keys = JwtKeys::from_config(&auth).unwrap();
+        let token = create_jwt_token(&keys, &42).unwrap();
+        assert_eq!(validate_jwt_token(&token, &keys).unwrap().user_id, 42);
+
+        auth.audience = "other".to_string();
+        let other = JwtKeys::from_config(&auth).unwrap();
+        assert!(validate_jwt_token(&token, &other).is_err());
+
+        fs::write(&secret_file, DEFAULT_SECRET).unwrap();
+        assert!(JwtKeys::from_config(&auth).is_err());
+        auth.dev_mode = true;
+        assert!(JwtKeys::from_config(&auth).is_ok());
+        fs::remove_file(secret_file).unwrap();
+    }
+}
@@ -36,6 +36,8 @@ pub struct ConfigBuilder {
     server: ServerSetup,
     storage: StorageSetup,
     data: plm_core::Data,
+    #[serde(default)]
+    auth: plm_core::Auth,
     // Add more options here as needed
 }
 impl Default for ConfigBuilder {
@@ -52,6 +54,7 @@ impl ConfigBuilder {
             // log_level: REGISTRY_LOG_LEVEL.to_string(),
             data: default_db_setup(),
             storage: default_storage_setup(),
+            auth: plm_core::Auth::default(),
         }
     }
 
@@ -71,6 +74,11 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn auth(&mut self, auth_setup: plm_core::Auth) -> &mut Self {
+        self.auth = auth_setup;
+        self
+    }
+
     // pub fn log_level(&mut self, log_level: &str) -> &mut Self {
     //     self.log_level = log_level.to_string();
     //     self
@@ -90,6 +98,7 @@ impl ConfigBuilder {
     pub fn build(self) -> plm_core::Config {
         plm_core::Config {
             data: Some(self.data),
+            auth: Some(self.auth),
             server: Some(self.server),
             storage: Some(plm_core::Storage {
                 storage_backend: Some(self.storage),
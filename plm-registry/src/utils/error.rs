@@ -19,4 +19,7 @@ pub enum RegistryError {
 
     #[error("Invalid file content: {0:?}")]
     InvalidFileContent(String),
+
+    #[error("Storage error: {0:?}")]
+    StorageError(String),
 }